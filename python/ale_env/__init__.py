@@ -0,0 +1,62 @@
+import enum
+import sys
+
+from . import ale_env as _ale_env
+from .ale_env import (
+    AleConfig,
+    Atari,
+    LoggerMode,
+    ReplayBuffer,
+    VecAtari,
+    action_meanings,
+    aggregate_scores,
+    derive_seed,
+    export_dataset,
+    game_info,
+    list_games,
+    list_suites,
+    logger_mode,
+    normalized_score,
+    read_trajectory,
+    replay_action_log,
+    set_logger_mode,
+    suite_games,
+    trajectory_video,
+)
+
+# worker processes of the processes backend run this interpreter on ale_env._worker
+if hasattr(_ale_env, "_set_worker_command"):
+    _ale_env._set_worker_command(sys.executable, ["-m", "ale_env._worker"])
+
+# ALE's 18 joystick actions, e.g. Action.UPRIGHTFIRE == 14
+Action = enum.IntEnum(
+    "Action", [(meaning, i) for i, meaning in enumerate(action_meanings())]
+)
+
+__all__ = [
+    "Action",
+    "AleConfig",
+    "Atari",
+    "LoggerMode",
+    "ReplayBuffer",
+    "VecAtari",
+    "action_meanings",
+    "aggregate_scores",
+    "derive_seed",
+    "export_dataset",
+    "game_info",
+    "list_games",
+    "list_suites",
+    "logger_mode",
+    "normalized_score",
+    "read_trajectory",
+    "replay_action_log",
+    "set_logger_mode",
+    "suite_games",
+    "trajectory_video",
+]
+
+# observation rings need POSIX shared memory
+if hasattr(_ale_env, "ObsRing"):
+    ObsRing = _ale_env.ObsRing
+    __all__.append("ObsRing")
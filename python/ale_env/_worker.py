@@ -0,0 +1,6 @@
+"""Entry point of the worker processes started by ``VecAtari(backend="processes")``."""
+
+from .ale_env import _worker_main
+
+if __name__ == "__main__":
+    _worker_main()
@@ -0,0 +1,210 @@
+"""Gymnasium ``Env`` and ``VectorEnv`` wrappers around ``Atari`` and ``VecAtari``.
+
+Requires the optional ``gymnasium`` dependency (``pip install ale-env[gymnasium]``).
+"""
+
+import gymnasium
+import numpy as np
+from gymnasium import spaces
+from gymnasium.vector import VectorEnv
+from gymnasium.vector.utils import batch_space
+
+from .ale_env import Atari, VecAtari
+
+try:
+    from gymnasium.vector import AutoresetMode
+
+    _SAME_STEP = AutoresetMode.SAME_STEP
+except ImportError:  # gymnasium < 1.1
+    _SAME_STEP = "SameStep"
+
+
+def _observation_space(screen_dim, gray_scale):
+    height, width = screen_dim
+    shape = (height, width) if gray_scale else (height, width, 3)
+    return spaces.Box(low=0, high=255, shape=shape, dtype=np.uint8)
+
+
+class AtariEnv(gymnasium.Env):
+    """Single Atari game following the Gymnasium ``Env`` API.
+
+    Actions are indices into the game's minimal action set, observations are
+    ``uint8`` screens of shape ``(210, 160)`` (gray scale) or ``(210, 160, 3)``.
+    """
+
+    metadata = {"render_modes": []}
+
+    def __init__(
+        self,
+        game,
+        max_frames=108_000,
+        gray_scale=True,
+        seed=None,
+        max_episode_steps=None,
+        config=None,
+    ):
+        self.game = game
+        self.ale = Atari(game, max_frames, gray_scale, seed, max_episode_steps, config)
+        self.action_set = self.ale.get_action_set()
+
+        self.observation_space = _observation_space(self.ale.screen_dim(), gray_scale)
+        self.action_space = spaces.Discrete(len(self.action_set))
+
+    def _obs(self):
+        return np.asarray(self.ale.obs(), dtype=np.uint8).reshape(
+            self.observation_space.shape
+        )
+
+    def reset(self, *, seed=None, options=None):
+        super().reset(seed=seed)
+        self.ale.reset(seed)
+        return self._obs(), {}
+
+    def step(self, action):
+        reward, terminal, truncation, life_loss = self.ale.step(
+            self.action_set[int(action)]
+        )
+        info = {"life_loss": life_loss}
+        if terminal or truncation:
+            info["episode_score"] = self.ale.get_score()
+        return self._obs(), float(reward), terminal, truncation, info
+
+
+class AtariVectorEnv(VectorEnv):
+    """Batch of Atari games following the Gymnasium ``VectorEnv`` API.
+
+    ``game`` is either one game, copied ``num_envs`` times, or a list with one
+    game per sub-environment. Mixing games with different minimal action sets
+    needs ``AleConfig(full_action_space=True)``; smaller screens are zero padded
+    to the largest one and ``info["game"]`` names the game of each sub-environment.
+
+    Finished sub-environments are reset within the same ``step`` call, so the
+    returned observation of a done environment is the first one of its next
+    episode; its last observation is in ``info["final_obs"]``, its infos in
+    ``info["final_info"]`` and its final score in ``info["episode_score"]``.
+
+    Sub-environment ``i`` is seeded with ``derive_seed(seed, i)``, so ``reset``
+    takes one base seed. A list of seeds is only accepted when all its entries
+    are the same base seed, or all ``None``.
+    """
+
+    metadata = {"autoreset_mode": _SAME_STEP}
+
+    def __init__(
+        self,
+        game,
+        num_envs=None,
+        max_frames=108_000,
+        gray_scale=True,
+        seed=0,
+        max_episode_steps=None,
+        config=None,
+        num_workers=None,
+        cpus=None,
+        recreate_failed_envs=False,
+        backend="threads",
+    ):
+        self.game = game
+        workers = {
+            "num_workers": num_workers,
+            "cpus": cpus,
+            "recreate_failed_envs": recreate_failed_envs,
+            "backend": backend,
+        }
+        if isinstance(game, str):
+            self.envs = VecAtari(
+                num_envs,
+                game,
+                max_frames,
+                gray_scale,
+                seed,
+                max_episode_steps,
+                config,
+                **workers,
+            )
+        else:
+            if num_envs is not None and num_envs != len(game):
+                raise ValueError(
+                    f"num_envs is {num_envs} but {len(game)} games were given"
+                )
+            self.envs = VecAtari.from_games(
+                list(game),
+                max_frames,
+                gray_scale,
+                seed,
+                max_episode_steps,
+                config,
+                **workers,
+            )
+        self.num_envs = len(self.envs.games())
+        self.action_set = np.asarray(self.envs.action_space(), dtype=np.int32)
+
+        self.single_observation_space = _observation_space(
+            self.envs.screen_dim(), gray_scale
+        )
+        self.single_action_space = spaces.Discrete(len(self.action_set))
+        self.observation_space = batch_space(
+            self.single_observation_space, self.num_envs
+        )
+        self.action_space = batch_space(self.single_action_space, self.num_envs)
+
+    def _obs(self, obs):
+        return np.asarray(obs, dtype=np.uint8).reshape(self.observation_space.shape)
+
+    def _base_seed(self, seed):
+        if seed is None or isinstance(seed, int):
+            return seed
+        seeds = list(seed)
+        if len(seeds) != self.num_envs or any(s != seeds[0] for s in seeds):
+            raise ValueError(
+                "Sub-environment seeds are derived from one base seed with "
+                f"derive_seed(seed, i); pass an int, not {seeds!r}"
+            )
+        return seeds[0]
+
+    def reset(self, *, seed=None, options=None):
+        seed = self._base_seed(seed)
+        super().reset(seed=seed)
+        # sub-environment i is reseeded with derive_seed(seed, i)
+        _, obs, _, _, _, _, _, _, _ = zip(*self.envs.reset(seed))
+        return self._obs(obs), {}
+
+    def step(self, actions):
+        actions = self.action_set[np.asarray(actions)]
+        _, obs, reward, terminal, truncation, life_loss, score, game, final_obs = zip(
+            *self.envs.step(actions.tolist())
+        )
+        # envs whose episode ended, with its score and last observation
+        done = np.asarray([o is not None for o in final_obs])
+        everywhere = np.ones(self.num_envs, dtype=bool)
+        infos = {
+            "life_loss": np.asarray(life_loss),
+            "_life_loss": everywhere,
+            "episode_score": np.asarray([s or 0 for s in score], dtype=np.int64),
+            "_episode_score": done,
+            "game": np.asarray(game),
+            "_game": everywhere,
+        }
+        if done.any():
+            shape = self.single_observation_space.shape
+            infos["final_obs"] = np.empty(self.num_envs, dtype=object)
+            for i in np.flatnonzero(done):
+                last = np.asarray(final_obs[i], dtype=np.uint8)
+                infos["final_obs"][i] = last.reshape(shape)
+            infos["_final_obs"] = done
+            infos["final_info"] = {
+                "life_loss": infos["life_loss"],
+                "_life_loss": done,
+                "episode_score": infos["episode_score"],
+                "_episode_score": done,
+                "game": infos["game"],
+                "_game": done,
+            }
+            infos["_final_info"] = done
+        return (
+            self._obs(obs),
+            np.asarray(reward, dtype=np.float64),
+            np.asarray(terminal),
+            np.asarray(truncation),
+            infos,
+        )
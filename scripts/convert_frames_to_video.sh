@@ -1 +0,0 @@
-ffmpeg -framerate 60 -i examples/frames/%05d.png -c:v libx264 -pix_fmt yuv420p -crf 23 output.mp4 -y
\ No newline at end of file
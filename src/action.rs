@@ -0,0 +1,155 @@
+use std::fmt;
+
+/// Joystick action, numbered as in ALE.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Action {
+    Noop = 0,
+    Fire = 1,
+    Up = 2,
+    Right = 3,
+    Left = 4,
+    Down = 5,
+    UpRight = 6,
+    UpLeft = 7,
+    DownRight = 8,
+    DownLeft = 9,
+    UpFire = 10,
+    RightFire = 11,
+    LeftFire = 12,
+    DownFire = 13,
+    UpRightFire = 14,
+    UpLeftFire = 15,
+    DownRightFire = 16,
+    DownLeftFire = 17,
+}
+
+/// Direction the joystick is pushed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Right,
+    Left,
+    Down,
+    UpRight,
+    UpLeft,
+    DownRight,
+    DownLeft,
+}
+
+impl Action {
+    /// All 18 actions, in ALE order, i.e. the legal action set.
+    pub const ALL: [Action; 18] = [
+        Action::Noop,
+        Action::Fire,
+        Action::Up,
+        Action::Right,
+        Action::Left,
+        Action::Down,
+        Action::UpRight,
+        Action::UpLeft,
+        Action::DownRight,
+        Action::DownLeft,
+        Action::UpFire,
+        Action::RightFire,
+        Action::LeftFire,
+        Action::DownFire,
+        Action::UpRightFire,
+        Action::UpLeftFire,
+        Action::DownRightFire,
+        Action::DownLeftFire,
+    ];
+
+    /// ALE's name of the action, e.g. `"UPRIGHTFIRE"`.
+    pub fn meaning(&self) -> &'static str {
+        use Action::*;
+        match self {
+            Noop => "NOOP",
+            Fire => "FIRE",
+            Up => "UP",
+            Right => "RIGHT",
+            Left => "LEFT",
+            Down => "DOWN",
+            UpRight => "UPRIGHT",
+            UpLeft => "UPLEFT",
+            DownRight => "DOWNRIGHT",
+            DownLeft => "DOWNLEFT",
+            UpFire => "UPFIRE",
+            RightFire => "RIGHTFIRE",
+            LeftFire => "LEFTFIRE",
+            DownFire => "DOWNFIRE",
+            UpRightFire => "UPRIGHTFIRE",
+            UpLeftFire => "UPLEFTFIRE",
+            DownRightFire => "DOWNRIGHTFIRE",
+            DownLeftFire => "DOWNLEFTFIRE",
+        }
+    }
+
+    /// Direction of the joystick, `None` when it is centered.
+    pub fn direction(&self) -> Option<Direction> {
+        use Action::*;
+        match self {
+            Noop | Fire => None,
+            Up | UpFire => Some(Direction::Up),
+            Right | RightFire => Some(Direction::Right),
+            Left | LeftFire => Some(Direction::Left),
+            Down | DownFire => Some(Direction::Down),
+            UpRight | UpRightFire => Some(Direction::UpRight),
+            UpLeft | UpLeftFire => Some(Direction::UpLeft),
+            DownRight | DownRightFire => Some(Direction::DownRight),
+            DownLeft | DownLeftFire => Some(Direction::DownLeft),
+        }
+    }
+
+    /// Whether the fire button is pressed.
+    pub fn fire(&self) -> bool {
+        *self == Action::Fire || *self as i32 >= Action::UpFire as i32
+    }
+
+    /// Combines a joystick direction and the fire button into an action.
+    pub fn from_parts(direction: Option<Direction>, fire: bool) -> Action {
+        use Action::*;
+        match (direction, fire) {
+            (None, false) => Noop,
+            (None, true) => Fire,
+            (Some(Direction::Up), false) => Up,
+            (Some(Direction::Right), false) => Right,
+            (Some(Direction::Left), false) => Left,
+            (Some(Direction::Down), false) => Down,
+            (Some(Direction::UpRight), false) => UpRight,
+            (Some(Direction::UpLeft), false) => UpLeft,
+            (Some(Direction::DownRight), false) => DownRight,
+            (Some(Direction::DownLeft), false) => DownLeft,
+            (Some(Direction::Up), true) => UpFire,
+            (Some(Direction::Right), true) => RightFire,
+            (Some(Direction::Left), true) => LeftFire,
+            (Some(Direction::Down), true) => DownFire,
+            (Some(Direction::UpRight), true) => UpRightFire,
+            (Some(Direction::UpLeft), true) => UpLeftFire,
+            (Some(Direction::DownRight), true) => DownRightFire,
+            (Some(Direction::DownLeft), true) => DownLeftFire,
+        }
+    }
+}
+
+impl From<Action> for i32 {
+    fn from(action: Action) -> i32 {
+        action as i32
+    }
+}
+
+impl TryFrom<i32> for Action {
+    type Error = String;
+
+    fn try_from(value: i32) -> Result<Action, String> {
+        usize::try_from(value)
+            .ok()
+            .and_then(|i| Action::ALL.get(i).copied())
+            .ok_or_else(|| format!("Invalid action {}, expected a value in [0, 17]", value))
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.meaning())
+    }
+}
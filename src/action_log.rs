@@ -0,0 +1,490 @@
+//! Episodes stored as the actions that produced them, replayed to verify them.
+//!
+//! ALE is deterministic given the ROM, the seed and the settings, so an episode is fully
+//! described by its header and actions. The rewards, the end of the episode and, optionally,
+//! a checksum of the screen after every step are kept to check a replay against. Logs are
+//! saved as text, e.g. to attach them to bug reports:
+//!
+//! ```text
+//! ale-action-log 1
+//! game=pong
+//! rom_md5=60e0ea3cbe0913d39803477945e9e5ec
+//! seed=42
+//! start=reseeded
+//! repeat_action_probability=0.25
+//! frame_skip=1
+//! color_averaging=false
+//! max_num_frames_per_episode=0
+//! terminal=true
+//! steps
+//! 3 0 5f1c34d2a9be07e1
+//! 11 1 0c3e98b2d1f04a77
+//! ```
+//!
+//! `mode` and `difficulty` lines follow `max_num_frames_per_episode` when they were set. Raw
+//! settings (`AleConfig::set`) are not stored, so envs using them cannot be logged. Each step
+//! line holds the ALE action number, the reward and, when checksums were recorded, the screen
+//! checksum.
+
+use crate::action::Action;
+use crate::atari::Atari;
+use crate::config::AleConfig;
+use crate::rom::BundledRom;
+use crate::trajectory::{EpisodeStart, Trajectory};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const FIRST_LINE: &str = "ale-action-log 1";
+// settings of a recorded episode that the header covers
+const TRAJECTORY_SETTINGS: &[&str] = &[
+    "game",
+    "rom",
+    "rom_md5",
+    "seed",
+    "start",
+    "height",
+    "width",
+    "channels",
+    "max_frames",
+    "max_episode_steps",
+    "repeat_action_probability",
+    "frame_skip",
+    "color_averaging",
+    "max_num_frames_per_episode",
+    "truncate_on_loss_of_life",
+    "full_action_space",
+    "mode",
+    "difficulty",
+];
+
+/// What an episode needs to be reproduced: the ROM, the seed and the settings that change
+/// the emulation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogHeader {
+    pub game: BundledRom,
+    /// MD5 of the ROM file, as ALE identifies ROMs.
+    pub rom_md5: String,
+    pub seed: i32,
+    /// `Created` or `Reseeded`; an episode continuing the random state cannot be replayed.
+    pub start: EpisodeStart,
+    pub repeat_action_probability: f32,
+    pub frame_skip: i32,
+    pub color_averaging: bool,
+    pub max_num_frames_per_episode: i32,
+    pub mode: Option<i32>,
+    pub difficulty: Option<i32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActionLog {
+    pub header: LogHeader,
+    pub actions: Vec<Action>,
+    pub rewards: Vec<i32>,
+    /// Whether the game was over after the last action.
+    pub terminal: bool,
+    /// `Atari::screen_checksum` after each step, if recorded.
+    pub checksums: Option<Vec<u64>>,
+}
+
+/// First difference between a replay and its log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplayMismatch {
+    /// The header has settings no env can be created with, e.g. an unsupported mode.
+    Header {
+        message: String,
+    },
+    Rom {
+        expected: String,
+        found: String,
+    },
+    Reward {
+        step: usize,
+        expected: i32,
+        found: i32,
+    },
+    /// The game was over at `step` although the log says otherwise, or the reverse.
+    GameOver {
+        step: usize,
+        expected: bool,
+    },
+    Screen {
+        step: usize,
+    },
+}
+
+impl fmt::Display for ReplayMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayMismatch::Header { message } => write!(f, "invalid log header: {}", message),
+            ReplayMismatch::Rom { expected, found } => {
+                write!(f, "ROM has MD5 {}, the log expects {}", found, expected)
+            }
+            ReplayMismatch::Reward {
+                step,
+                expected,
+                found,
+            } => write!(
+                f,
+                "step {} gave reward {}, the log expects {}",
+                step, found, expected
+            ),
+            ReplayMismatch::GameOver { step, expected } => match expected {
+                true => write!(f, "game is not over after step {}", step),
+                false => write!(f, "game is over after step {}", step),
+            },
+            ReplayMismatch::Screen { step } => {
+                write!(f, "screen after step {} differs from the log", step)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayMismatch {}
+
+impl ActionLog {
+    pub fn new(header: LogHeader, screen_checksums: bool) -> ActionLog {
+        ActionLog {
+            header,
+            actions: Vec::new(),
+            rewards: Vec::new(),
+            terminal: false,
+            checksums: screen_checksums.then(Vec::new),
+        }
+    }
+
+    /// Log of an episode recorded with `Atari::start_recording`, which must have started
+    /// from its seed.
+    pub fn from_trajectory(trajectory: &Trajectory) -> Result<ActionLog, String> {
+        if trajectory.start == EpisodeStart::Continued {
+            return Err("The episode did not start from its seed".to_string());
+        }
+        if let Some((key, _)) = trajectory
+            .settings
+            .iter()
+            .find(|(key, _)| !TRAJECTORY_SETTINGS.contains(&key.as_str()))
+        {
+            return Err(format!(
+                "The trajectory has the raw setting {}, which action logs do not store",
+                key
+            ));
+        }
+        let setting = |key: &str| {
+            trajectory
+                .setting(key)
+                .ok_or_else(|| format!("The trajectory has no {} setting", key))
+        };
+        let parse_err = |key: &str| format!("Invalid {} in the trajectory", key);
+        let optional = |key: &str| {
+            trajectory
+                .setting(key)
+                .map(|value| value.parse::<i32>().map_err(|_| parse_err(key)))
+                .transpose()
+        };
+        let header = LogHeader {
+            game: trajectory.game,
+            rom_md5: setting("rom_md5")?.to_string(),
+            seed: trajectory.seed,
+            start: trajectory.start,
+            repeat_action_probability: setting("repeat_action_probability")?
+                .parse()
+                .map_err(|_| parse_err("repeat_action_probability"))?,
+            frame_skip: setting("frame_skip")?
+                .parse()
+                .map_err(|_| parse_err("frame_skip"))?,
+            color_averaging: setting("color_averaging")?
+                .parse()
+                .map_err(|_| parse_err("color_averaging"))?,
+            max_num_frames_per_episode: setting("max_num_frames_per_episode")?
+                .parse()
+                .map_err(|_| parse_err("max_num_frames_per_episode"))?,
+            mode: optional("mode")?,
+            difficulty: optional("difficulty")?,
+        };
+        // the last row holds the final observation, without an action
+        let steps = trajectory.len().saturating_sub(1);
+        Ok(ActionLog {
+            header,
+            actions: trajectory.actions[..steps].to_vec(),
+            rewards: trajectory.rewards[..steps].to_vec(),
+            terminal: trajectory.terminals.last().copied().unwrap_or(false),
+            checksums: None,
+        })
+    }
+
+    pub(crate) fn push(&mut self, action: Action, reward: i32, checksum: u64) {
+        self.actions.push(action);
+        self.rewards.push(reward);
+        if let Some(checksums) = self.checksums.as_mut() {
+            checksums.push(checksum);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    pub fn score(&self) -> i32 {
+        self.rewards.iter().sum()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<ActionLog> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Settings to create an env that replays the log with.
+    pub fn config(&self) -> AleConfig {
+        let mut config = AleConfig::default();
+        config.repeat_action_probability = self.header.repeat_action_probability;
+        config.frame_skip = self.header.frame_skip;
+        config.color_averaging = self.header.color_averaging;
+        config.max_num_frames_per_episode = self.header.max_num_frames_per_episode;
+        config.mode = self.header.mode;
+        config.difficulty = self.header.difficulty;
+        config
+    }
+
+    /// Plays the log back on a new env and checks it step by step; returns the score.
+    pub fn replay(&self) -> Result<i32, ReplayMismatch> {
+        let header = &self.header;
+        let mut env = Atari::try_new(
+            header.game.name(),
+            i32::MAX as u32,
+            true,
+            Some(header.seed),
+            None,
+            Some(self.config()),
+        )
+        .map_err(|message| ReplayMismatch::Header { message })?;
+        if env.rom_md5() != header.rom_md5 {
+            return Err(ReplayMismatch::Rom {
+                expected: header.rom_md5.clone(),
+                found: env.rom_md5().to_string(),
+            });
+        }
+        match header.start {
+            EpisodeStart::Created => env.reset(None),
+            _ => env.reset(Some(header.seed)),
+        }
+
+        for (step, (&action, &expected)) in self.actions.iter().zip(&self.rewards).enumerate() {
+            let (reward, terminal, _, _) = env.step(action);
+            if reward != expected {
+                return Err(ReplayMismatch::Reward {
+                    step,
+                    expected,
+                    found: reward,
+                });
+            }
+            let last = step + 1 == self.len();
+            if terminal != (last && self.terminal) {
+                return Err(ReplayMismatch::GameOver {
+                    step,
+                    expected: !terminal,
+                });
+            }
+            if let Some(checksums) = &self.checksums {
+                if env.screen_checksum() != checksums[step] {
+                    return Err(ReplayMismatch::Screen { step });
+                }
+            }
+        }
+        Ok(env.get_score())
+    }
+}
+
+impl fmt::Display for ActionLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = &self.header;
+        writeln!(f, "{}", FIRST_LINE)?;
+        writeln!(f, "game={}", header.game.name())?;
+        writeln!(f, "rom_md5={}", header.rom_md5)?;
+        writeln!(f, "seed={}", header.seed)?;
+        writeln!(f, "start={}", header.start.name())?;
+        writeln!(
+            f,
+            "repeat_action_probability={}",
+            header.repeat_action_probability
+        )?;
+        writeln!(f, "frame_skip={}", header.frame_skip)?;
+        writeln!(f, "color_averaging={}", header.color_averaging)?;
+        writeln!(
+            f,
+            "max_num_frames_per_episode={}",
+            header.max_num_frames_per_episode
+        )?;
+        if let Some(mode) = header.mode {
+            writeln!(f, "mode={}", mode)?;
+        }
+        if let Some(difficulty) = header.difficulty {
+            writeln!(f, "difficulty={}", difficulty)?;
+        }
+        writeln!(f, "terminal={}", self.terminal)?;
+        writeln!(f, "steps")?;
+        for (step, (&action, reward)) in self.actions.iter().zip(&self.rewards).enumerate() {
+            match &self.checksums {
+                Some(checksums) => {
+                    writeln!(f, "{} {} {:016x}", action as i32, reward, checksums[step])?
+                }
+                None => writeln!(f, "{} {}", action as i32, reward)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for ActionLog {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+        if lines.next() != Some(FIRST_LINE) {
+            return Err("Not an action log".to_string());
+        }
+        let mut values = Vec::new();
+        for line in lines.by_ref() {
+            if line == "steps" {
+                break;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid action log line {:?}", line))?;
+            values.push((key, value));
+        }
+        let get = |key: &str| {
+            values
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| *v)
+                .ok_or_else(|| format!("The action log has no {}", key))
+        };
+        let bad = |key: &str| format!("Invalid {} in the action log", key);
+        let optional = |key: &str| {
+            get(key)
+                .ok()
+                .map(|value| value.parse::<i32>().map_err(|_| bad(key)))
+                .transpose()
+        };
+        let header = LogHeader {
+            game: get("game")?.parse().map_err(|_| bad("game"))?,
+            rom_md5: get("rom_md5")?.to_string(),
+            seed: get("seed")?.parse().map_err(|_| bad("seed"))?,
+            start: match get("start")? {
+                "created" => EpisodeStart::Created,
+                "reseeded" => EpisodeStart::Reseeded,
+                _ => return Err(bad("start")),
+            },
+            repeat_action_probability: get("repeat_action_probability")?
+                .parse()
+                .map_err(|_| bad("repeat_action_probability"))?,
+            frame_skip: get("frame_skip")?.parse().map_err(|_| bad("frame_skip"))?,
+            color_averaging: get("color_averaging")?
+                .parse()
+                .map_err(|_| bad("color_averaging"))?,
+            max_num_frames_per_episode: get("max_num_frames_per_episode")?
+                .parse()
+                .map_err(|_| bad("max_num_frames_per_episode"))?,
+            mode: optional("mode")?,
+            difficulty: optional("difficulty")?,
+        };
+        let terminal = get("terminal")?.parse().map_err(|_| bad("terminal"))?;
+
+        let mut log = ActionLog::new(header, false);
+        log.terminal = terminal;
+        for (i, line) in lines.enumerate() {
+            let bad_step = || format!("Invalid step {} in the action log", i);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (action, reward, checksum) = match fields[..] {
+                [action, reward] => (action, reward, None),
+                [action, reward, checksum] => (action, reward, Some(checksum)),
+                _ => return Err(bad_step()),
+            };
+            // checksums are kept for every step or none
+            if i == 0 {
+                log.checksums = checksum.map(|_| Vec::new());
+            }
+            if checksum.is_some() != log.checksums.is_some() {
+                return Err(bad_step());
+            }
+            let action = action.parse::<i32>().map_err(|_| bad_step())?;
+            let checksum = match checksum {
+                Some(checksum) => u64::from_str_radix(checksum, 16).map_err(|_| bad_step())?,
+                None => 0,
+            };
+            log.push(
+                Action::try_from(action)?,
+                reward.parse().map_err(|_| bad_step())?,
+                checksum,
+            );
+        }
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(screen_checksums: bool) -> ActionLog {
+        let header = LogHeader {
+            game: BundledRom::Pong,
+            rom_md5: "60e0ea3cbe0913d39803477945e9e5ec".to_string(),
+            seed: -42,
+            start: EpisodeStart::Reseeded,
+            repeat_action_probability: 0.25,
+            frame_skip: 4,
+            color_averaging: true,
+            max_num_frames_per_episode: 108_000,
+            mode: Some(1),
+            difficulty: None,
+        };
+        let mut log = ActionLog::new(header, screen_checksums);
+        log.push(Action::Fire, 0, 0x5f1c_34d2_a9be_07e1);
+        log.push(Action::UpRight, -1, 0x0c3e);
+        log.push(Action::Noop, 1, u64::MAX);
+        log.terminal = true;
+        log
+    }
+
+    #[test]
+    fn text_round_trip() {
+        for screen_checksums in [true, false] {
+            let log = log(screen_checksums);
+            let text = log.to_string();
+            assert!(text.starts_with("ale-action-log 1\ngame=pong\n"));
+            assert!(text.contains("\nmode=1\nterminal=true\nsteps\n"));
+            assert!(!text.contains("difficulty"));
+            assert_eq!(text.parse::<ActionLog>().unwrap(), log);
+        }
+    }
+
+    #[test]
+    fn invalid_text_is_rejected() {
+        let text = log(true).to_string();
+        assert!("ale-action-log 2\n".parse::<ActionLog>().is_err());
+        assert!(text
+            .replace("start=reseeded", "start=continued")
+            .parse::<ActionLog>()
+            .is_err());
+        assert!(text
+            .replace("frame_skip=4\n", "")
+            .parse::<ActionLog>()
+            .is_err());
+        // checksums are kept for every step or none
+        assert!(text
+            .replace(" 0000000000000c3e\n", "\n")
+            .parse::<ActionLog>()
+            .is_err());
+    }
+}
@@ -1,8 +1,16 @@
-use pyo3::prelude::*;
+use crate::action::Action;
+use crate::action_log::{ActionLog, LogHeader};
+use crate::config::{AleConfig, SettingValue};
+use crate::logger::init_logger_mode;
+use crate::rom::{BundledRom, ParseRomError};
+use crate::trajectory::{EpisodeStart, Recorder, RecordingOptions};
+use crate::video::{FrameInfo, VideoOptions, VideoRecorder, VideoWriter};
+use md5::{Digest, Md5};
 use std::env;
-use std::ffi::CString;
-use std::os::raw::c_int;
-use std::path::Path;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::os::raw::{c_char, c_int};
+use std::path::{Path, PathBuf};
 use std::ptr::null_mut;
 use tempdir;
 
@@ -15,33 +23,82 @@ pub use crate::bindings::root::{
     getLegalActionSet, getLegalActionSize, getMinimalActionSet, getMinimalActionSize, getRAM,
     getRAMSize, getScreen, getScreenGrayscale, getScreenHeight, getScreenRGB, getScreenWidth,
     getString, lives, loadROM, loadState, reset_game, restoreState, restoreSystemState,
-    saveScreenPNG, saveState, setBool, setDifficulty, setFloat, setInt, setLoggerMode, setMode,
-    setString, ALE_del, ALE_new,
+    saveScreenPNG, saveState, setBool, setDifficulty, setFloat, setInt, setMode, setString,
+    ALE_del, ALE_new,
 };
 
-#[pyclass]
 pub struct Atari {
     ale: *mut ALEInterface,
-    action_set: Vec<i32>,
+    rom: BundledRom,
+    // keeps the ROM copy alive so it can be reloaded on reseeding
+    _rom_dir: tempdir::TempDir,
+    rom_path: PathBuf,
+    rom_md5: String,
+    seed: i32,
+    action_set: Vec<Action>,
     screen_data: Vec<u8>,
     screen_size: (usize, usize),
     score: i32,
     lives: i32,
-    frame_count: i32,
+    episode_steps: i32,
     max_frames: i32,
+    max_episode_steps: Option<i32>,
     gray_scale: bool,
+    config: AleConfig,
+    // whether an episode was played since the emulator was created
+    started: bool,
+    episode_start: EpisodeStart,
+    recorder: Option<Recorder>,
+    action_log: Option<ActionLog>,
+    // log of the episode ended by the last reset, until taken
+    finished_action_log: Option<ActionLog>,
+    video: Option<VideoRecorder>,
 }
 
 unsafe impl Send for Atari {}
 unsafe impl Sync for Atari {}
 
-#[pymethods]
 impl Atari {
-    #[new]
-    pub fn new(game: &str, max_frames: u32, gray_scale: bool, seed: Option<i32>) -> Atari {
+    // max_frames truncates episodes in emulator frames, max_episode_steps in step calls;
+    // panics where try_new returns an error
+    pub fn new(
+        game: &str,
+        max_frames: u32,
+        gray_scale: bool,
+        seed: Option<i32>,
+        max_episode_steps: Option<u32>,
+        config: Option<AleConfig>,
+    ) -> Atari {
+        Atari::try_new(
+            game,
+            max_frames,
+            gray_scale,
+            seed,
+            max_episode_steps,
+            config,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // create an env, or return an error on an unknown game, an invalid config, a mode or
+    // difficulty the game does not support, or when the ROM cannot be copied
+    pub fn try_new(
+        game: &str,
+        max_frames: u32,
+        gray_scale: bool,
+        seed: Option<i32>,
+        max_episode_steps: Option<u32>,
+        config: Option<AleConfig>,
+    ) -> Result<Atari, String> {
+        let config = config.unwrap_or_default();
+        config
+            .validate()
+            .map_err(|e| format!("Invalid ALE config: {}", e))?;
+        let rom: BundledRom = game.parse().map_err(|e: ParseRomError| e.to_string())?;
+
         // save ROM to temp dir
-        let dir = tempdir::TempDir::new("ale-rs").expect("Create temp dir failed");
-        let rom = BundledRom::name2rom(game);
+        let dir = tempdir::TempDir::new("ale-rs")
+            .map_err(|e| format!("Create temp dir failed: {}", e))?;
         let des_path = dir.path().join(rom.filename());
 
         let absolute_path = Path::new(env!("CARGO_MANIFEST_DIR")).join(file!());
@@ -52,78 +109,141 @@ impl Atari {
             .expect("Cannot find project directory")
             .join("roms")
             .join(rom.filename());
-        std::fs::copy(&src_path, &des_path).expect("Copy ROM to tempdir failed");
+        let rom_data = std::fs::read(&src_path)
+            .map_err(|e| format!("Read ROM {} failed: {}", src_path.display(), e))?;
+        std::fs::write(&des_path, &rom_data)
+            .map_err(|e| format!("Copy ROM to tempdir failed: {}", e))?;
+        let rom_md5 = format!("{:x}", Md5::digest(&rom_data));
 
-        let (ale, action_set, screen_size) = unsafe {
-            setLoggerMode(LoggerMode::Error as c_int);
-
-            // create ALEInterface
-            let ale = ALE_new();
-            assert!(ale != null_mut(), "Create ALEInterface failed");
-
-            // set no action repeat
-            let key = CString::new("repeat_action_probability").expect("Create Cstring key failed");
-            setFloat(ale, key.as_ptr(), 0.0);
-
-            // seed the game
-            let key = CString::new("random_seed").expect("Create Cstring key failed");
-            match seed {
-                Some(v) => setInt(ale, key.as_ptr(), v),
-                None => (),
+        init_logger_mode();
+        // create ALEInterface
+        let ale = unsafe { ALE_new() };
+        if ale.is_null() {
+            return Err("Create ALEInterface failed".to_string());
+        }
+        let (action_set, screen_size, seed) =
+            match unsafe { load_rom(ale, game, &des_path, seed, &config) } {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    unsafe { ALE_del(ale) };
+                    return Err(e);
+                }
             };
-
-            // load ROM
-            let rom_path_c_str = CString::new(des_path.to_str().unwrap()).expect("Invalid path");
-            loadROM(ale, rom_path_c_str.as_ptr());
-
-            // get minimal action set
-            let action_dim = getMinimalActionSize(ale);
-            let mut action_set = vec![0i32; action_dim as usize];
-            getMinimalActionSet(ale, action_set.as_mut_ptr());
-            let screen_size = (getScreenHeight(ale) as usize, getScreenWidth(ale) as usize);
-
-            (ale, action_set, screen_size)
-        };
         let score = 0;
         let lives = 0;
-        let frame_count = 0;
+        let episode_steps = 0;
         let screen_data = match gray_scale {
             true => vec![0u8; screen_size.0 * screen_size.1],
             false => vec![0u8; screen_size.0 * screen_size.1 * 3],
         };
         let max_frames = max_frames as i32;
+        let max_episode_steps = max_episode_steps.map(|v| v as i32);
 
-        Atari {
+        Ok(Atari {
             ale,
+            rom,
+            _rom_dir: dir,
+            rom_path: des_path,
+            rom_md5,
+            seed,
             action_set,
             screen_data,
             screen_size,
             score,
             lives,
-            frame_count,
+            episode_steps,
             max_frames,
+            max_episode_steps,
             gray_scale,
-        }
+            config,
+            started: false,
+            episode_start: EpisodeStart::Created,
+            recorder: None,
+            action_log: None,
+            finished_action_log: None,
+            video: None,
+        })
     }
 
-    pub fn reset(&mut self) {
+    // reset the game; a given seed reloads the ROM so that ALE picks it up
+    pub fn reset(&mut self, seed: Option<i32>) {
+        // an action log covers a single episode
+        if let Some(log) = self.action_log.take() {
+            self.finished_action_log = Some(log);
+        }
         unsafe {
+            if let Some(seed) = seed {
+                let key = CString::new("random_seed").expect("Create Cstring key failed");
+                setInt(self.ale, key.as_ptr(), seed);
+                let rom_path_c_str =
+                    CString::new(self.rom_path.to_str().unwrap()).expect("Invalid path");
+                loadROM(self.ale, rom_path_c_str.as_ptr());
+                // reloading falls back to the default mode and difficulty
+                if let Some(mode) = self.config.mode {
+                    setMode(self.ale, mode);
+                }
+                if let Some(difficulty) = self.config.difficulty {
+                    setDifficulty(self.ale, difficulty);
+                }
+                self.seed = seed;
+            }
             reset_game(self.ale);
             self.lives = lives(self.ale);
         }
         self.score = 0;
-        self.frame_count = 0;
+        self.episode_steps = 0;
+        self.episode_start = match (seed, self.started) {
+            (Some(_), _) => EpisodeStart::Reseeded,
+            (None, false) => EpisodeStart::Created,
+            (None, true) => EpisodeStart::Continued,
+        };
+        self.started = true;
+
+        if let Some(mut recorder) = self.recorder.take() {
+            let obs = self.obs();
+            recorder.begin(&self.recording_header(), &obs, self.lives);
+            self.recorder = Some(recorder);
+        }
+        self.push_video_frame(0, None);
     }
 
     // return step information: (reward, terminal, truncation, life loss)
-    pub fn step(&mut self, action: i32) -> (i32, bool, bool, bool) {
-        let (reward, terminal, cur_lives) =
-            unsafe { (act(self.ale, action), game_over(self.ale), lives(self.ale)) };
-        self.frame_count += 1;
+    pub fn step(&mut self, action: Action) -> (i32, bool, bool, bool) {
+        let (reward, terminal, cur_lives, episode_frames) = unsafe {
+            (
+                act(self.ale, action as i32),
+                game_over(self.ale),
+                lives(self.ale),
+                getEpisodeFrameNumber(self.ale),
+            )
+        };
+        self.episode_steps += 1;
+        self.started = true;
         let life_loss = cur_lives < self.lives;
-        let truncation = self.frame_count >= self.max_frames;
+        let truncation = episode_frames >= self.max_frames
+            || self
+                .max_episode_steps
+                .is_some_and(|max_steps| self.episode_steps >= max_steps)
+            || (self.config.truncate_on_loss_of_life && life_loss);
         self.lives = cur_lives;
         self.score += reward;
+
+        if let Some(mut recorder) = self.recorder.take() {
+            if recorder.is_recording() {
+                let obs = self.obs();
+                recorder.step(action, reward, &obs, cur_lives, terminal, truncation);
+            }
+            self.recorder = Some(recorder);
+        }
+        let checksum = match &self.action_log {
+            Some(log) if log.checksums.is_some() => self.screen_checksum(),
+            _ => 0,
+        };
+        if let Some(log) = self.action_log.as_mut() {
+            log.push(action, reward, checksum);
+            log.terminal = terminal;
+        }
+        self.push_video_frame(reward, Some(action));
         (reward, terminal, truncation, life_loss)
     }
 
@@ -137,378 +257,476 @@ impl Atari {
         self.screen_data.clone()
     }
 
+    // write the observation into obs, which must have the size of the screen
+    pub fn obs_into(&mut self, obs: &mut [u8]) {
+        assert_eq!(
+            obs.len(),
+            self.screen_data.len(),
+            "Observation buffer has the wrong size"
+        );
+        unsafe {
+            match self.gray_scale {
+                true => getScreenGrayscale(self.ale, obs.as_mut_ptr()),
+                false => getScreenRGB(self.ale, obs.as_mut_ptr()),
+            }
+        }
+    }
+
     // return (height, width) of screen
-    pub fn screen_dim(&mut self) -> (usize, usize) {
+    pub fn screen_dim(&self) -> (usize, usize) {
         self.screen_size
     }
 
-    pub fn get_action_set(&mut self) -> Vec<i32> {
+    pub fn rom(&self) -> BundledRom {
+        self.rom
+    }
+
+    pub fn get_action_set(&self) -> Vec<Action> {
         self.action_set.clone()
     }
 
-    pub fn get_score(&mut self) -> i32 {
+    pub fn get_score(&self) -> i32 {
         self.score
     }
 
-    pub fn close(&mut self) {
+    // return the number of emulator frames elapsed in the current episode
+    pub fn episode_frame_number(&self) -> i32 {
+        unsafe { getEpisodeFrameNumber(self.ale) }
+    }
+
+    // return the number of step calls made in the current episode
+    pub fn episode_steps(&self) -> i32 {
+        self.episode_steps
+    }
+
+    pub fn get_config(&self) -> AleConfig {
+        self.config.clone()
+    }
+
+    pub fn available_modes(&self) -> Vec<i32> {
+        unsafe { available_modes(self.ale) }
+    }
+
+    pub fn available_difficulties(&self) -> Vec<i32> {
+        unsafe { available_difficulties(self.ale) }
+    }
+
+    // raw access to ALE settings; most settings only take effect when the ROM is (re)loaded,
+    // keys and values with nul bytes are an error
+    pub fn get_int(&self, key: &str) -> Result<i32, String> {
+        let key = setting_key(key)?;
+        Ok(unsafe { getInt(self.ale, key.as_ptr()) })
+    }
+
+    pub fn get_float(&self, key: &str) -> Result<f32, String> {
+        let key = setting_key(key)?;
+        Ok(unsafe { getFloat(self.ale, key.as_ptr()) })
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<bool, String> {
+        let key = setting_key(key)?;
+        Ok(unsafe { getBool(self.ale, key.as_ptr()) })
+    }
+
+    pub fn get_string(&self, key: &str) -> Result<String, String> {
+        let key = setting_key(key)?;
+        Ok(unsafe {
+            CStr::from_ptr(getString(self.ale, key.as_ptr()))
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+
+    pub fn set_int(&mut self, key: &str, value: i32) -> Result<(), String> {
+        let key = setting_key(key)?;
+        unsafe { setInt(self.ale, key.as_ptr(), value) };
+        Ok(())
+    }
+
+    pub fn set_float(&mut self, key: &str, value: f32) -> Result<(), String> {
+        let key = setting_key(key)?;
+        unsafe { setFloat(self.ale, key.as_ptr(), value) };
+        Ok(())
+    }
+
+    pub fn set_bool(&mut self, key: &str, value: bool) -> Result<(), String> {
+        let key = setting_key(key)?;
+        unsafe { setBool(self.ale, key.as_ptr(), value) };
+        Ok(())
+    }
+
+    pub fn set_string(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = CString::new(value)
+            .map_err(|_| format!("setting {:?} has a value with a nul byte", key))?;
+        let key = setting_key(key)?;
+        unsafe { setString(self.ale, key.as_ptr(), value.as_ptr()) };
+        Ok(())
+    }
+
+    // serialize the emulator state, e.g. to save human starts
+    pub fn clone_state(&self) -> Vec<u8> {
         unsafe {
-            ALE_del(self.ale);
+            let state = cloneState(self.ale);
+            let len = encodeStateLen(state);
+            let mut buf = vec![0u8; len as usize];
+            encodeState(state, buf.as_mut_ptr() as *mut c_char, len);
+            deleteState(state);
+            buf
         }
     }
-}
 
-impl Drop for Atari {
-    fn drop(&mut self) {
-        self.close();
+    // restore a state from clone_state; score and steps of the episode count from here
+    pub fn restore_state(&mut self, state: &[u8]) -> Result<(), String> {
+        let len = c_int::try_from(state.len()).map_err(|_| "ALE state is too long".to_string())?;
+        unsafe {
+            let state = decodeState(state.as_ptr() as *const c_char, len);
+            if state.is_null() {
+                return Err("Decode ALE state failed".to_string());
+            }
+            restoreState(self.ale, state);
+            deleteState(state);
+            self.lives = lives(self.ale);
+        }
+        self.score = 0;
+        self.episode_steps = 0;
+        Ok(())
+    }
+
+    // write every episode from the next reset on to its own file in dir, see the trajectory
+    // module for the format; a running recording is stopped first
+    pub fn start_recording(
+        &mut self,
+        dir: impl AsRef<Path>,
+        options: RecordingOptions,
+    ) -> io::Result<()> {
+        self.record(dir.as_ref(), None, options)
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        dir: &Path,
+        env: Option<usize>,
+        options: RecordingOptions,
+    ) -> io::Result<()> {
+        self.stop_recording()?;
+        std::fs::create_dir_all(dir)?;
+        self.recorder = Some(Recorder::new(dir, env, options));
+        Ok(())
+    }
+
+    // write the open episode as truncated and return the first error met while recording
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    // game, seed, observation shape and settings stored with a recorded episode
+    fn recording_header(&self) -> Vec<(String, String)> {
+        let config = &self.config;
+        let mut header = vec![
+            ("game", self.rom.name().to_string()),
+            ("rom", self.rom.filename().to_string()),
+            ("rom_md5", self.rom_md5.clone()),
+            ("seed", self.seed.to_string()),
+            ("start", self.episode_start.name().to_string()),
+            ("height", self.screen_size.0.to_string()),
+            ("width", self.screen_size.1.to_string()),
+            (
+                "channels",
+                if self.gray_scale { "1" } else { "3" }.to_string(),
+            ),
+            ("max_frames", self.max_frames.to_string()),
+            (
+                "repeat_action_probability",
+                config.repeat_action_probability.to_string(),
+            ),
+            ("frame_skip", config.frame_skip.to_string()),
+            ("color_averaging", config.color_averaging.to_string()),
+            (
+                "max_num_frames_per_episode",
+                config.max_num_frames_per_episode.to_string(),
+            ),
+            (
+                "truncate_on_loss_of_life",
+                config.truncate_on_loss_of_life.to_string(),
+            ),
+            ("full_action_space", config.full_action_space.to_string()),
+        ];
+        if let Some(max_episode_steps) = self.max_episode_steps {
+            header.push(("max_episode_steps", max_episode_steps.to_string()));
+        }
+        if let Some(mode) = config.mode {
+            header.push(("mode", mode.to_string()));
+        }
+        if let Some(difficulty) = config.difficulty {
+            header.push(("difficulty", difficulty.to_string()));
+        }
+        let mut header: Vec<(String, String)> = header
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+        // raw settings override the typed ones, as when they are applied
+        for (key, value) in config.extra() {
+            let value = match value {
+                SettingValue::Int(v) => v.to_string(),
+                SettingValue::Float(v) => v.to_string(),
+                SettingValue::Bool(v) => v.to_string(),
+                SettingValue::Str(v) => v.clone(),
+            };
+            header.retain(|(k, _)| k != key);
+            header.push((key.clone(), value));
+        }
+        header
     }
+
+    // reset with seed and log the actions of the episode from here on, with a checksum of the
+    // screen after each step if asked, so that ActionLog::replay can reproduce it; the log is
+    // closed by the next reset. Fails without resetting when raw settings are set, as the log
+    // header does not store them
+    pub fn start_action_log(&mut self, seed: i32, screen_checksums: bool) -> Result<(), String> {
+        if let Some((key, _)) = self.config.extra().first() {
+            return Err(format!(
+                "Action logs do not store raw settings such as {}",
+                key
+            ));
+        }
+        self.action_log = None;
+        self.reset(Some(seed));
+        self.finished_action_log = None;
+        let config = &self.config;
+        let header = LogHeader {
+            game: self.rom,
+            rom_md5: self.rom_md5.clone(),
+            seed,
+            start: EpisodeStart::Reseeded,
+            repeat_action_probability: config.repeat_action_probability,
+            frame_skip: config.frame_skip,
+            color_averaging: config.color_averaging,
+            max_num_frames_per_episode: config.max_num_frames_per_episode,
+            mode: config.mode,
+            difficulty: config.difficulty,
+        };
+        self.action_log = Some(ActionLog::new(header, screen_checksums));
+        Ok(())
+    }
+
+    // stop logging actions and return the log, or the log closed by the last reset
+    pub fn take_action_log(&mut self) -> Option<ActionLog> {
+        self.action_log
+            .take()
+            .or_else(|| self.finished_action_log.take())
+    }
+
+    // RGB screen, whatever the observation type
+    pub fn rgb_screen(&self) -> Vec<u8> {
+        let mut screen = vec![0u8; self.screen_size.0 * self.screen_size.1 * 3];
+        unsafe { getScreenRGB(self.ale, screen.as_mut_ptr()) };
+        screen
+    }
+
+    // save the screen as a PNG file with ALE's own encoder; the file is written next to path and
+    // renamed over it, so that a failed save leaves an existing file untouched
+    pub fn save_screen(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        let partial_c_str = partial
+            .to_str()
+            .and_then(|partial| CString::new(partial).ok())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Invalid screenshot path")
+            })?;
+        // ALE does not report failures, so check that the file was written
+        let _ = std::fs::remove_file(&partial);
+        unsafe { saveScreenPNG(self.ale, partial_c_str.as_ptr()) };
+        match partial.is_file() {
+            true => std::fs::rename(&partial, path),
+            false => Err(io::Error::other(format!(
+                "ALE could not save the screen to {}",
+                path.display()
+            ))),
+        }
+    }
+
+    // PNG encoded RGB screen, whatever the observation type
+    pub fn screen_png(&self) -> io::Result<Vec<u8>> {
+        let (height, width) = self.screen_size;
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgb_screen())?;
+        writer.finish()?;
+        Ok(png)
+    }
+
+    // write the RGB screen to a GIF, APNG or Y4M video after every reset and step from now on,
+    // see the video module; a running video is finished first
+    pub fn start_video(&mut self, path: impl AsRef<Path>, options: VideoOptions) -> io::Result<()> {
+        self.finish_video()?;
+        let writer = VideoWriter::create(path, self.screen_size, 3, options)?;
+        self.video = Some(VideoRecorder::new(writer));
+        self.push_video_frame(0, None);
+        Ok(())
+    }
+
+    // complete the video and return the first error met while writing it
+    pub fn finish_video(&mut self) -> io::Result<()> {
+        match self.video.take() {
+            Some(video) => video.finish(),
+            None => Ok(()),
+        }
+    }
+
+    // reward led to the current screen, action is the one that was taken before it
+    fn push_video_frame(&mut self, reward: i32, action: Option<Action>) {
+        if self.video.is_none() {
+            return;
+        }
+        let frame = self.rgb_screen();
+        let info = FrameInfo {
+            step: self.episode_steps as u32,
+            reward,
+            score: self.score,
+            action,
+        };
+        if let Some(video) = self.video.as_mut() {
+            video.push(&frame, &info);
+        }
+    }
+
+    // MD5 of the ROM file as hex, which is how ALE identifies ROMs; computed when the ROM is
+    // copied
+    pub fn rom_md5(&self) -> &str {
+        &self.rom_md5
+    }
+
+    // 64-bit FNV-1a hash of the raw palette indices of the screen, independent of the
+    // observation type
+    pub fn screen_checksum(&self) -> u64 {
+        let mut screen = vec![0u8; self.screen_size.0 * self.screen_size.1];
+        unsafe { getScreen(self.ale, screen.as_mut_ptr()) };
+        screen.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    // return the seed the emulator is currently running with
+    pub fn get_seed(&self) -> i32 {
+        self.seed
+    }
+
+    // release the emulator, the same as dropping the env; recordings and videos are finished
+    // and their errors ignored
+    pub fn close(self) {}
 }
 
-pub enum LoggerMode {
-    Info = 0,
-    Warning = 1,
-    Error = 2,
+unsafe fn available_modes(ale: *mut ALEInterface) -> Vec<i32> {
+    let mut modes = vec![0i32; getAvailableModesSize(ale) as usize];
+    getAvailableModes(ale, modes.as_mut_ptr());
+    modes
 }
 
-pub enum BundledRom {
-    Adventure,
-    AirRaid,
-    Alien,
-    Amidar,
-    Assault,
-    Asterix,
-    Asteroids,
-    Atlantis,
-    Atlantis2,
-    Backgammon,
-    BankHeist,
-    BasicMath,
-    BattleZone,
-    BeamRider,
-    Berzerk,
-    Blackjack,
-    Bowling,
-    Boxing,
-    Breakout,
-    Carnival,
-    Casino,
-    Centipede,
-    ChopperCommand,
-    Combat,
-    CrazyClimber,
-    Crossbow,
-    Darkchambers,
-    Defender,
-    DemonAttack,
-    DonkeyKong,
-    DoubleDunk,
-    Earthworld,
-    ElevatorAction,
-    Enduro,
-    Entombed,
-    Et,
-    FishingDerby,
-    FlagCapture,
-    Freeway,
-    Frogger,
-    Frostbite,
-    Galaxian,
-    Gopher,
-    Gravitar,
-    Hangman,
-    HauntedHouse,
-    Hero,
-    HumanCannonball,
-    IceHockey,
-    Jamesbond,
-    JourneyEscape,
-    Joust,
-    Kaboom,
-    Kangaroo,
-    KeystoneKapers,
-    KingKong,
-    Klax,
-    Koolaid,
-    Krull,
-    KungFuMaster,
-    LaserGates,
-    LostLuggage,
-    MarioBros,
-    MazeCraze,
-    MiniatureGolf,
-    MontezumaRevenge,
-    MrDo,
-    MsPacman,
-    NameThisGame,
-    Othello,
-    Pacman,
-    Phoenix,
-    Pitfall,
-    Pitfall2,
-    Pong,
-    Pooyan,
-    PrivateEye,
-    Qbert,
-    Riverraid,
-    RoadRunner,
-    Robotank,
-    Seaquest,
-    SirLancelot,
-    Skiing,
-    Solaris,
-    SpaceInvaders,
-    SpaceWar,
-    StarGunner,
-    Superman,
-    Surround,
-    Tennis,
-    Tetris,
-    TicTacToe3d,
-    TimePilot,
-    Trondead,
-    Turmoil,
-    Tutankham,
-    UpNDown,
-    Venture,
-    VideoCheckers,
-    VideoChess,
-    VideoCube,
-    VideoPinball,
-    Warlords,
-    WizardOfWor,
-    WordZapper,
-    YarsRevenge,
-    Zaxxon,
+unsafe fn available_difficulties(ale: *mut ALEInterface) -> Vec<i32> {
+    let mut difficulties = vec![0i32; getAvailableDifficultiesSize(ale) as usize];
+    getAvailableDifficulties(ale, difficulties.as_mut_ptr());
+    difficulties
 }
 
-impl BundledRom {
-    pub fn name2rom(name: &str) -> BundledRom {
-        use BundledRom::*;
-        match name {
-            "adventure" => Adventure,
-            "air_raid" => AirRaid,
-            "alien" => Alien,
-            "amidar" => Amidar,
-            "assault" => Assault,
-            "asterix" => Asterix,
-            "asteroids" => Asteroids,
-            "atlantis" => Atlantis,
-            "atlantis2" => Atlantis2,
-            "backgammon" => Backgammon,
-            "bank_heist" => BankHeist,
-            "basic_math" => BasicMath,
-            "battle_zone" => BattleZone,
-            "beam_rider" => BeamRider,
-            "berzerk" => Berzerk,
-            "blackjack" => Blackjack,
-            "bowling" => Bowling,
-            "boxing" => Boxing,
-            "breakout" => Breakout,
-            "carnival" => Carnival,
-            "casino" => Casino,
-            "centipede" => Centipede,
-            "chopper_command" => ChopperCommand,
-            "combat" => Combat,
-            "crazy_climber" => CrazyClimber,
-            "crossbow" => Crossbow,
-            "darkchambers" => Darkchambers,
-            "defender" => Defender,
-            "demon_attack" => DemonAttack,
-            "donkey_kong" => DonkeyKong,
-            "double_dunk" => DoubleDunk,
-            "earthworld" => Earthworld,
-            "elevator_action" => ElevatorAction,
-            "enduro" => Enduro,
-            "entombed" => Entombed,
-            "et" => Et,
-            "fishing_derby" => FishingDerby,
-            "flag_capture" => FlagCapture,
-            "freeway" => Freeway,
-            "frogger" => Frogger,
-            "frostbite" => Frostbite,
-            "galaxian" => Galaxian,
-            "gopher" => Gopher,
-            "gravitar" => Gravitar,
-            "hangman" => Hangman,
-            "haunted_house" => HauntedHouse,
-            "hero" => Hero,
-            "human_cannonball" => HumanCannonball,
-            "ice_hockey" => IceHockey,
-            "jamesbond" => Jamesbond,
-            "journey_escape" => JourneyEscape,
-            "joust" => Joust,
-            "kaboom" => Kaboom,
-            "kangaroo" => Kangaroo,
-            "keystone_kapers" => KeystoneKapers,
-            "king_kong" => KingKong,
-            "klax" => Klax,
-            "koolaid" => Koolaid,
-            "krull" => Krull,
-            "kung_fu_master" => KungFuMaster,
-            "laser_gates" => LaserGates,
-            "lost_luggage" => LostLuggage,
-            "mario_bros" => MarioBros,
-            "maze_craze" => MazeCraze,
-            "miniature_golf" => MiniatureGolf,
-            "montezuma_revenge" => MontezumaRevenge,
-            "mr_do" => MrDo,
-            "ms_pacman" => MsPacman,
-            "name_this_game" => NameThisGame,
-            "othello" => Othello,
-            "pacman" => Pacman,
-            "phoenix" => Phoenix,
-            "pitfall" => Pitfall,
-            "pitfall2" => Pitfall2,
-            "pong" => Pong,
-            "pooyan" => Pooyan,
-            "private_eye" => PrivateEye,
-            "qbert" => Qbert,
-            "riverraid" => Riverraid,
-            "road_runner" => RoadRunner,
-            "robotank" => Robotank,
-            "seaquest" => Seaquest,
-            "sir_lancelot" => SirLancelot,
-            "skiing" => Skiing,
-            "solaris" => Solaris,
-            "space_invaders" => SpaceInvaders,
-            "space_war" => SpaceWar,
-            "star_gunner" => StarGunner,
-            "superman" => Superman,
-            "surround" => Surround,
-            "tennis" => Tennis,
-            "tetris" => Tetris,
-            "tic_tac_toe_3d" => TicTacToe3d,
-            "time_pilot" => TimePilot,
-            "trondead" => Trondead,
-            "turmoil" => Turmoil,
-            "tutankham" => Tutankham,
-            "up_n_down" => UpNDown,
-            "venture" => Venture,
-            "video_checkers" => VideoCheckers,
-            "video_chess" => VideoChess,
-            "video_cube" => VideoCube,
-            "video_pinball" => VideoPinball,
-            "warlords" => Warlords,
-            "wizard_of_wor" => WizardOfWor,
-            "word_zapper" => WordZapper,
-            "yars_revenge" => YarsRevenge,
-            "zaxxon" => Zaxxon,
-            _ => panic!("ROM {} not supported", name),
+fn setting_key(key: &str) -> Result<CString, String> {
+    CString::new(key).map_err(|_| format!("setting key {:?} contains a nul byte", key))
+}
+
+// action set, screen size and the seed ALE runs with
+type LoadedRom = (Vec<Action>, (usize, usize), i32);
+
+// apply the settings and seed, load the ROM and select mode and difficulty
+unsafe fn load_rom(
+    ale: *mut ALEInterface,
+    game: &str,
+    rom_path: &Path,
+    seed: Option<i32>,
+    config: &AleConfig,
+) -> Result<LoadedRom, String> {
+    // apply settings, these are only picked up by loadROM
+    config.apply(ale);
+
+    // seed the game
+    let key = CString::new("random_seed").expect("Create Cstring key failed");
+    if let Some(v) = seed {
+        setInt(ale, key.as_ptr(), v);
+    }
+
+    // load ROM
+    let rom_path_c_str = CString::new(rom_path.to_str().unwrap()).expect("Invalid path");
+    loadROM(ale, rom_path_c_str.as_ptr());
+
+    // ALE falls back to its default seed when none is given
+    let seed = getInt(ale, key.as_ptr());
+
+    // select mode and difficulty, ALE throws on unsupported values
+    if let Some(mode) = config.mode {
+        let modes = available_modes(ale);
+        if !modes.contains(&mode) {
+            return Err(format!(
+                "Mode {} not supported by {}, available modes: {:?}",
+                mode, game, modes
+            ));
+        }
+        setMode(ale, mode);
+    }
+    if let Some(difficulty) = config.difficulty {
+        let difficulties = available_difficulties(ale);
+        if !difficulties.contains(&difficulty) {
+            return Err(format!(
+                "Difficulty {} not supported by {}, available difficulties: {:?}",
+                difficulty, game, difficulties
+            ));
         }
+        setDifficulty(ale, difficulty);
+    }
+    if config.mode.is_some() || config.difficulty.is_some() {
+        reset_game(ale);
     }
 
-    /// Returns the filename that the ROM should be named, in order for the ALE to pick up on it and
-    /// use the correct settings.
-    pub fn filename(&self) -> &'static str {
-        use BundledRom::*;
-        match self {
-            Adventure => "adventure.bin",
-            AirRaid => "air_raid.bin",
-            Alien => "alien.bin",
-            Amidar => "amidar.bin",
-            Assault => "assault.bin",
-            Asterix => "asterix.bin",
-            Asteroids => "asteroids.bin",
-            Atlantis => "atlantis.bin",
-            Atlantis2 => "atlantis2.bin",
-            Backgammon => "backgammon.bin",
-            BankHeist => "bank_heist.bin",
-            BasicMath => "basic_math.bin",
-            BattleZone => "battle_zone.bin",
-            BeamRider => "beam_rider.bin",
-            Berzerk => "berzerk.bin",
-            Blackjack => "blackjack.bin",
-            Bowling => "bowling.bin",
-            Boxing => "boxing.bin",
-            Breakout => "breakout.bin",
-            Carnival => "carnival.bin",
-            Casino => "casino.bin",
-            Centipede => "centipede.bin",
-            ChopperCommand => "chopper_command.bin",
-            Combat => "combat.bin",
-            CrazyClimber => "crazy_climber.bin",
-            Crossbow => "crossbow.bin",
-            Darkchambers => "darkchambers.bin",
-            Defender => "defender.bin",
-            DemonAttack => "demon_attack.bin",
-            DonkeyKong => "donkey_kong.bin",
-            DoubleDunk => "double_dunk.bin",
-            Earthworld => "earthworld.bin",
-            ElevatorAction => "elevator_action.bin",
-            Enduro => "enduro.bin",
-            Entombed => "entombed.bin",
-            Et => "et.bin",
-            FishingDerby => "fishing_derby.bin",
-            FlagCapture => "flag_capture.bin",
-            Freeway => "freeway.bin",
-            Frogger => "frogger.bin",
-            Frostbite => "frostbite.bin",
-            Galaxian => "galaxian.bin",
-            Gopher => "gopher.bin",
-            Gravitar => "gravitar.bin",
-            Hangman => "hangman.bin",
-            HauntedHouse => "haunted_house.bin",
-            Hero => "hero.bin",
-            HumanCannonball => "human_cannonball.bin",
-            IceHockey => "ice_hockey.bin",
-            Jamesbond => "jamesbond.bin",
-            JourneyEscape => "journey_escape.bin",
-            Joust => "joust.bin",
-            Kaboom => "kaboom.bin",
-            Kangaroo => "kangaroo.bin",
-            KeystoneKapers => "keystone_kapers.bin",
-            KingKong => "king_kong.bin",
-            Klax => "klax.bin",
-            Koolaid => "koolaid.bin",
-            Krull => "krull.bin",
-            KungFuMaster => "kung_fu_master.bin",
-            LaserGates => "laser_gates.bin",
-            LostLuggage => "lost_luggage.bin",
-            MarioBros => "mario_bros.bin",
-            MazeCraze => "maze_craze.bin",
-            MiniatureGolf => "miniature_golf.bin",
-            MontezumaRevenge => "montezuma_revenge.bin",
-            MrDo => "mr_do.bin",
-            MsPacman => "ms_pacman.bin",
-            NameThisGame => "name_this_game.bin",
-            Othello => "othello.bin",
-            Pacman => "pacman.bin",
-            Phoenix => "phoenix.bin",
-            Pitfall => "pitfall.bin",
-            Pitfall2 => "pitfall2.bin",
-            Pong => "pong.bin",
-            Pooyan => "pooyan.bin",
-            PrivateEye => "private_eye.bin",
-            Qbert => "qbert.bin",
-            Riverraid => "riverraid.bin",
-            RoadRunner => "road_runner.bin",
-            Robotank => "robotank.bin",
-            Seaquest => "seaquest.bin",
-            SirLancelot => "sir_lancelot.bin",
-            Skiing => "skiing.bin",
-            Solaris => "solaris.bin",
-            SpaceInvaders => "space_invaders.bin",
-            SpaceWar => "space_war.bin",
-            StarGunner => "star_gunner.bin",
-            Superman => "superman.bin",
-            Surround => "surround.bin",
-            Tennis => "tennis.bin",
-            Tetris => "tetris.bin",
-            TicTacToe3d => "tic_tac_toe_3d.bin",
-            TimePilot => "time_pilot.bin",
-            Trondead => "trondead.bin",
-            Turmoil => "turmoil.bin",
-            Tutankham => "tutankham.bin",
-            UpNDown => "up_n_down.bin",
-            Venture => "venture.bin",
-            VideoCheckers => "video_checkers.bin",
-            VideoChess => "video_chess.bin",
-            VideoCube => "video_cube.bin",
-            VideoPinball => "video_pinball.bin",
-            Warlords => "warlords.bin",
-            WizardOfWor => "wizard_of_wor.bin",
-            WordZapper => "word_zapper.bin",
-            YarsRevenge => "yars_revenge.bin",
-            Zaxxon => "zaxxon.bin",
+    // get minimal or full action set
+    let action_set = match config.full_action_space {
+        true => {
+            let mut action_set = vec![0i32; getLegalActionSize(ale) as usize];
+            getLegalActionSet(ale, action_set.as_mut_ptr());
+            action_set
+        }
+        false => {
+            let mut action_set = vec![0i32; getMinimalActionSize(ale) as usize];
+            getMinimalActionSet(ale, action_set.as_mut_ptr());
+            action_set
+        }
+    };
+    let action_set: Vec<Action> = action_set
+        .into_iter()
+        .map(|a| Action::try_from(a).expect("ALE returned an unknown action"))
+        .collect();
+    let screen_size = (getScreenHeight(ale) as usize, getScreenWidth(ale) as usize);
+    Ok((action_set, screen_size, seed))
+}
+
+impl Drop for Atari {
+    fn drop(&mut self) {
+        let _ = self.stop_recording();
+        let _ = self.finish_video();
+        if !self.ale.is_null() {
+            unsafe {
+                ALE_del(self.ale);
+            }
+            self.ale = null_mut();
         }
     }
 }
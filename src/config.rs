@@ -0,0 +1,148 @@
+use crate::bindings::root::{ale::ALEInterface, setBool, setFloat, setInt, setString};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use std::ffi::CString;
+
+/// Value of a raw ALE setting, applied with the matching typed setter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingValue {
+    Int(i32),
+    Float(f32),
+    Bool(bool),
+    Str(String),
+}
+
+/// ALE settings applied when an `Atari` loads its ROM.
+///
+/// The defaults follow the previous behaviour of this crate rather than ALE's own defaults:
+/// sticky actions are disabled (`repeat_action_probability = 0.0`), every action is emulated for
+/// one frame and the game's default mode and difficulty are used.
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug)]
+pub struct AleConfig {
+    /// Probability of repeating the previous action instead of the requested one.
+    pub repeat_action_probability: f32,
+    /// Number of emulator frames each `act` call runs, summing their rewards.
+    pub frame_skip: i32,
+    /// Average the last two frames into the observation to remove flickering.
+    pub color_averaging: bool,
+    /// Hard cap ALE itself places on an episode, in frames; 0 means unlimited.
+    /// Reaching it is reported as game over, unlike the `max_frames` truncation of `Atari`.
+    pub max_num_frames_per_episode: i32,
+    /// Game mode, checked against the modes the ROM supports.
+    pub mode: Option<i32>,
+    /// Game difficulty, checked against the difficulties the ROM supports.
+    pub difficulty: Option<i32>,
+    /// Report truncation whenever a life is lost.
+    pub truncate_on_loss_of_life: bool,
+    /// Expose all 18 actions instead of the game's minimal action set, so that envs running
+    /// different games share one action space.
+    pub full_action_space: bool,
+    // raw settings for keys not covered above, applied in insertion order
+    extra: Vec<(String, SettingValue)>,
+}
+
+impl Default for AleConfig {
+    fn default() -> Self {
+        AleConfig {
+            repeat_action_probability: 0.0,
+            frame_skip: 1,
+            color_averaging: false,
+            max_num_frames_per_episode: 0,
+            mode: None,
+            difficulty: None,
+            truncate_on_loss_of_life: false,
+            full_action_space: false,
+            extra: Vec::new(),
+        }
+    }
+}
+
+impl AleConfig {
+    /// Checks the settings that can be validated without loading a ROM.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.repeat_action_probability) {
+            return Err(format!(
+                "repeat_action_probability must be in [0, 1], got {}",
+                self.repeat_action_probability
+            ));
+        }
+        if self.frame_skip < 1 {
+            return Err(format!(
+                "frame_skip must be at least 1, got {}",
+                self.frame_skip
+            ));
+        }
+        if self.max_num_frames_per_episode < 0 {
+            return Err(format!(
+                "max_num_frames_per_episode must be non-negative, got {}",
+                self.max_num_frames_per_episode
+            ));
+        }
+        for (key, value) in self.extra.iter() {
+            if key.contains('\0') {
+                return Err(format!("setting key {:?} contains a nul byte", key));
+            }
+            if matches!(value, SettingValue::Str(v) if v.contains('\0')) {
+                return Err(format!("setting {:?} has a value with a nul byte", key));
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a raw setting, overriding any typed field that maps to the same key.
+    pub fn set(&mut self, key: &str, value: SettingValue) {
+        self.extra.retain(|(k, _)| k != key);
+        self.extra.push((key.to_string(), value));
+    }
+
+    pub fn set_int(&mut self, key: &str, value: i32) {
+        self.set(key, SettingValue::Int(value));
+    }
+
+    pub fn set_float(&mut self, key: &str, value: f32) {
+        self.set(key, SettingValue::Float(value));
+    }
+
+    pub fn set_bool(&mut self, key: &str, value: bool) {
+        self.set(key, SettingValue::Bool(value));
+    }
+
+    pub fn set_string(&mut self, key: &str, value: &str) {
+        self.set(key, SettingValue::Str(value.to_string()));
+    }
+
+    /// Raw settings added with `set`.
+    pub fn extra(&self) -> &[(String, SettingValue)] {
+        &self.extra
+    }
+
+    // write settings to ALE, must happen before loadROM to take effect
+    pub(crate) unsafe fn apply(&self, ale: *mut ALEInterface) {
+        let key = |k: &str| CString::new(k).expect("Create Cstring key failed");
+        setFloat(
+            ale,
+            key("repeat_action_probability").as_ptr(),
+            self.repeat_action_probability,
+        );
+        setInt(ale, key("frame_skip").as_ptr(), self.frame_skip);
+        setBool(ale, key("color_averaging").as_ptr(), self.color_averaging);
+        setInt(
+            ale,
+            key("max_num_frames_per_episode").as_ptr(),
+            self.max_num_frames_per_episode,
+        );
+        for (k, value) in self.extra.iter() {
+            let k = key(k);
+            match value {
+                SettingValue::Int(v) => setInt(ale, k.as_ptr(), *v),
+                SettingValue::Float(v) => setFloat(ale, k.as_ptr(), *v),
+                SettingValue::Bool(v) => setBool(ale, k.as_ptr(), *v),
+                SettingValue::Str(v) => {
+                    let v = CString::new(v.as_str()).expect("Create Cstring value failed");
+                    setString(ale, k.as_ptr(), v.as_ptr())
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,566 @@
+//! Offline RL datasets in an RLDS-like layout, exported from recorded trajectories.
+//!
+//! A dataset directory holds shards `shard-00000`, `shard-00001`, ... with one `.npy` file per
+//! field, readable with `numpy.load(path, mmap_mode="r")`, and an index of its episodes:
+//!
+//! | file               | dtype     | shape per step                    |
+//! |--------------------|-----------|-----------------------------------|
+//! | `observation.npy`  | `uint8`   | `(height, width)` or `(height, width, 3)` |
+//! | `action.npy`       | `int32`   | `()`                              |
+//! | `reward.npy`       | `float32` | `()`                              |
+//! | `discount.npy`     | `float32` | `()`                              |
+//! | `is_first.npy`     | `bool`    | `()`                              |
+//! | `is_last.npy`      | `bool`    | `()`                              |
+//! | `is_terminal.npy`  | `bool`    | `()`                              |
+//! | `lives.npy`        | `int32`   | `()`                              |
+//!
+//! As in RLDS, an episode of `T` actions has `T + 1` steps: step `t` holds the observation, the
+//! action taken from it and the reward and discount that followed, and the last step holds the
+//! final observation with zero action, reward and discount. The discount is 0 for the action
+//! that ended the game. Episodes are never split across shards.
+//!
+//! `index.tsv` is tab separated, after a `# ale-env dataset 1` line, with one row per episode
+//! and the columns `episode shard offset length game seed start score terminal`, `offset` and
+//! `length` counting steps within the shard.
+//!
+//! Files are written to `<name>.partial` and renamed to `<name>` once complete, the index last,
+//! so a writer dropped without `finish` leaves no index and its dataset does not open.
+
+use crate::action::Action;
+use crate::replay::ReplayBuffer;
+use crate::rom::BundledRom;
+use crate::trajectory::{EpisodeStart, Trajectory};
+use crate::vec_atari::StepResult;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const INDEX_FIRST_LINE: &str = "# ale-env dataset 1";
+const INDEX_COLUMNS: &str = "episode\tshard\toffset\tlength\tgame\tseed\tstart\tscore\tterminal";
+// bytes of an npy header, enough for any shape this module writes
+const NPY_HEADER_SIZE: usize = 128;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DatasetOptions {
+    /// Steps after which a new shard is started, unless the shard holds a single episode.
+    pub shard_steps: usize,
+}
+
+impl Default for DatasetOptions {
+    fn default() -> Self {
+        DatasetOptions {
+            shard_steps: 100_000,
+        }
+    }
+}
+
+/// An episode of a dataset, as listed in its index.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpisodeEntry {
+    pub shard: usize,
+    /// First step of the episode in its shard.
+    pub offset: usize,
+    /// Steps of the episode, one more than its actions.
+    pub length: usize,
+    pub game: BundledRom,
+    pub seed: i32,
+    pub start: EpisodeStart,
+    pub score: i32,
+    pub terminal: bool,
+}
+
+pub struct DatasetWriter {
+    dir: PathBuf,
+    options: DatasetOptions,
+    index: BufWriter<File>,
+    index_partial: PathBuf,
+    shard: Option<ShardWriter>,
+    shards: usize,
+    episodes: usize,
+    obs_shape: Option<Vec<usize>>,
+}
+
+impl DatasetWriter {
+    /// Starts a dataset in `dir`, which must not hold one already.
+    pub fn create(dir: impl AsRef<Path>, options: DatasetOptions) -> io::Result<DatasetWriter> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        if dir.join("index.tsv").exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already holds a dataset", dir.display()),
+            ));
+        }
+        let index_partial = partial_path(&dir.join("index.tsv"));
+        let mut index = BufWriter::new(File::create(&index_partial)?);
+        writeln!(index, "{}", INDEX_FIRST_LINE)?;
+        writeln!(index, "{}", INDEX_COLUMNS)?;
+        Ok(DatasetWriter {
+            dir,
+            options,
+            index,
+            index_partial,
+            shard: None,
+            shards: 0,
+            episodes: 0,
+            obs_shape: None,
+        })
+    }
+
+    /// Appends an episode; all episodes of a dataset have the same observation shape.
+    pub fn add(&mut self, trajectory: &Trajectory) -> io::Result<()> {
+        let length = trajectory.len();
+        if length == 0 {
+            return Ok(());
+        }
+        let mut obs_shape = vec![trajectory.screen_size.0, trajectory.screen_size.1];
+        if trajectory.channels == 3 {
+            obs_shape.push(3);
+        }
+        if self.obs_shape.get_or_insert(obs_shape.clone()) != &obs_shape {
+            return Err(invalid(format!(
+                "Episode observations have shape {:?}, the dataset has {:?}",
+                obs_shape, self.obs_shape
+            )));
+        }
+        let full = self.shard.as_ref().is_some_and(|shard| {
+            shard.steps > 0 && shard.steps + length > self.options.shard_steps
+        });
+        if full {
+            self.shard.take().unwrap().finish()?;
+        }
+        if self.shard.is_none() {
+            let dir = self.dir.join(format!("shard-{:05}", self.shards));
+            self.shard = Some(ShardWriter::create(&dir, &obs_shape)?);
+            self.shards += 1;
+        }
+
+        let shard = self.shard.as_mut().unwrap();
+        let offset = shard.steps;
+        shard.add(trajectory)?;
+        writeln!(
+            self.index,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.episodes,
+            self.shards - 1,
+            offset,
+            length,
+            trajectory.game.name(),
+            trajectory.seed,
+            trajectory.start.name(),
+            trajectory.score(),
+            trajectory.terminals[length - 1],
+        )?;
+        self.episodes += 1;
+        Ok(())
+    }
+
+    /// Completes the last shard and the index; returns the number of episodes.
+    pub fn finish(mut self) -> io::Result<usize> {
+        if let Some(shard) = self.shard.take() {
+            shard.finish()?;
+        }
+        self.index.flush()?;
+        fs::rename(&self.index_partial, self.dir.join("index.tsv"))?;
+        Ok(self.episodes)
+    }
+}
+
+/// Exports the trajectories recorded in `trajectory_dir`, in file name order, to a dataset in
+/// `dataset_dir`; returns the number of episodes.
+pub fn export_dataset(
+    trajectory_dir: impl AsRef<Path>,
+    dataset_dir: impl AsRef<Path>,
+    options: DatasetOptions,
+) -> io::Result<usize> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(trajectory_dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "traj"));
+    paths.sort();
+    let mut writer = DatasetWriter::create(dataset_dir, options)?;
+    for path in paths {
+        writer.add(&Trajectory::read(path)?)?;
+    }
+    writer.finish()
+}
+
+/// A dataset written by `DatasetWriter`, read an episode at a time.
+pub struct Dataset {
+    dir: PathBuf,
+    episodes: Vec<EpisodeEntry>,
+}
+
+impl Dataset {
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Dataset> {
+        let dir = dir.as_ref().to_path_buf();
+        let mut lines = BufReader::new(File::open(dir.join("index.tsv"))?).lines();
+        if lines.next().transpose()?.as_deref() != Some(INDEX_FIRST_LINE) {
+            return Err(invalid("Not a dataset index".to_string()));
+        }
+        lines.next().transpose()?;
+        let mut episodes = Vec::new();
+        for line in lines {
+            let line = line?;
+            let bad = || invalid(format!("Invalid dataset index line {:?}", line));
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [_, shard, offset, length, game, seed, start, score, terminal] = fields[..] else {
+                return Err(bad());
+            };
+            // an episode has at least its first observation
+            let length = length.parse().map_err(|_| bad())?;
+            if length == 0 {
+                return Err(bad());
+            }
+            episodes.push(EpisodeEntry {
+                shard: shard.parse().map_err(|_| bad())?,
+                offset: offset.parse().map_err(|_| bad())?,
+                length,
+                game: game.parse().map_err(|_| bad())?,
+                seed: seed.parse().map_err(|_| bad())?,
+                start: match start {
+                    "created" => EpisodeStart::Created,
+                    "reseeded" => EpisodeStart::Reseeded,
+                    "continued" => EpisodeStart::Continued,
+                    _ => return Err(bad()),
+                },
+                score: score.parse().map_err(|_| bad())?,
+                terminal: terminal.parse().map_err(|_| bad())?,
+            });
+        }
+        Ok(Dataset { dir, episodes })
+    }
+
+    pub fn episodes(&self) -> &[EpisodeEntry] {
+        &self.episodes
+    }
+
+    /// Total number of steps.
+    pub fn len(&self) -> usize {
+        self.episodes.iter().map(|episode| episode.length).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.episodes.is_empty()
+    }
+
+    /// Reads episode `i` back as a trajectory, without settings.
+    pub fn read_episode(&self, i: usize) -> io::Result<Trajectory> {
+        let entry = self
+            .episodes
+            .get(i)
+            .ok_or_else(|| invalid(format!("Episode {} is out of range", i)))?;
+        let shard = self.dir.join(format!("shard-{:05}", entry.shard));
+        let rows = entry.offset..entry.offset + entry.length;
+        let (obs_shape, obs) = read_rows(&shard.join("observation.npy"), "|u1", rows.clone())?;
+        let actions = read_rows(&shard.join("action.npy"), "<i4", rows.clone())?.1;
+        let rewards = read_rows(&shard.join("reward.npy"), "<f4", rows.clone())?.1;
+        let lives = read_rows(&shard.join("lives.npy"), "<i4", rows.clone())?.1;
+        let is_last = read_rows(&shard.join("is_last.npy"), "|b1", rows.clone())?.1;
+        let is_terminal = read_rows(&shard.join("is_terminal.npy"), "|b1", rows)?.1;
+        let (screen_size, channels) = match obs_shape[..] {
+            [height, width] => ((height, width), 1),
+            [height, width, 3] => ((height, width), 3),
+            _ => return Err(invalid("Observations have an invalid shape".to_string())),
+        };
+
+        let actions = actions
+            .chunks_exact(4)
+            .map(|a| Action::try_from(i32::from_le_bytes(a.try_into().unwrap())).map_err(invalid))
+            .collect::<io::Result<Vec<_>>>()?;
+        let rewards = rewards
+            .chunks_exact(4)
+            .map(|r| f32::from_le_bytes(r.try_into().unwrap()) as i32)
+            .collect();
+        let lives = lives
+            .chunks_exact(4)
+            .map(|l| i32::from_le_bytes(l.try_into().unwrap()))
+            .collect();
+        Ok(Trajectory {
+            game: entry.game,
+            seed: entry.seed,
+            start: entry.start,
+            env: None,
+            screen_size,
+            channels,
+            settings: Vec::new(),
+            obs,
+            actions,
+            rewards,
+            lives,
+            terminals: is_terminal.iter().map(|&b| b != 0).collect(),
+            truncations: is_last
+                .iter()
+                .zip(&is_terminal)
+                .map(|(&last, &terminal)| last != 0 && terminal == 0)
+                .collect(),
+        })
+    }
+
+    /// Adds the given episodes to a single-env replay buffer, to sample them in training.
+    pub fn fill(&self, buffer: &mut ReplayBuffer, episodes: &[usize]) -> io::Result<()> {
+        assert_eq!(
+            buffer.num_envs(),
+            1,
+            "Datasets fill single-env replay buffers"
+        );
+        for &i in episodes {
+            let trajectory = self.read_episode(i)?;
+            let steps = trajectory.len() - 1;
+            let result = |t: usize, reward: i32, done: bool| StepResult {
+                index: 0,
+                game: trajectory.game,
+                obs: trajectory.obs_at(t).to_vec(),
+                reward,
+                terminal: done && trajectory.terminals[steps],
+                truncation: done && !trajectory.terminals[steps],
+                life_loss: t > 0 && trajectory.lives[t] < trajectory.lives[t - 1],
+                score: None,
+                final_obs: done.then(|| trajectory.obs_at(t).to_vec()),
+            };
+            buffer.reset(&[result(0, 0, false)]);
+            for t in 0..steps {
+                let next = result(t + 1, trajectory.rewards[t], t + 1 == steps);
+                buffer.add(&[trajectory.actions[t]], &[next]);
+            }
+        }
+        Ok(())
+    }
+}
+
+struct ShardWriter {
+    steps: usize,
+    observation: NpyWriter,
+    action: NpyWriter,
+    reward: NpyWriter,
+    discount: NpyWriter,
+    is_first: NpyWriter,
+    is_last: NpyWriter,
+    is_terminal: NpyWriter,
+    lives: NpyWriter,
+}
+
+impl ShardWriter {
+    fn create(dir: &Path, obs_shape: &[usize]) -> io::Result<ShardWriter> {
+        fs::create_dir_all(dir)?;
+        let npy = |name: &str, descr, shape: &[usize]| {
+            NpyWriter::create(&dir.join(format!("{}.npy", name)), descr, shape)
+        };
+        Ok(ShardWriter {
+            steps: 0,
+            observation: npy("observation", "|u1", obs_shape)?,
+            action: npy("action", "<i4", &[])?,
+            reward: npy("reward", "<f4", &[])?,
+            discount: npy("discount", "<f4", &[])?,
+            is_first: npy("is_first", "|b1", &[])?,
+            is_last: npy("is_last", "|b1", &[])?,
+            is_terminal: npy("is_terminal", "|b1", &[])?,
+            lives: npy("lives", "<i4", &[])?,
+        })
+    }
+
+    fn add(&mut self, trajectory: &Trajectory) -> io::Result<()> {
+        let length = trajectory.len();
+        let terminal = trajectory.terminals[length - 1];
+        for t in 0..length {
+            let last = t + 1 == length;
+            let discount = match (last, terminal && t + 2 == length) {
+                (false, false) => 1.0f32,
+                _ => 0.0,
+            };
+            let (action, reward) = match last {
+                true => (0, 0.0f32),
+                false => (trajectory.actions[t] as i32, trajectory.rewards[t] as f32),
+            };
+            self.observation.write_row(trajectory.obs_at(t))?;
+            self.action.write_row(&action.to_le_bytes())?;
+            self.reward.write_row(&reward.to_le_bytes())?;
+            self.discount.write_row(&discount.to_le_bytes())?;
+            self.is_first.write_row(&[(t == 0) as u8])?;
+            self.is_last.write_row(&[last as u8])?;
+            self.is_terminal.write_row(&[(last && terminal) as u8])?;
+            self.lives.write_row(&trajectory.lives[t].to_le_bytes())?;
+        }
+        self.steps += length;
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<()> {
+        for npy in [
+            self.observation,
+            self.action,
+            self.reward,
+            self.discount,
+            self.is_first,
+            self.is_last,
+            self.is_terminal,
+            self.lives,
+        ] {
+            npy.finish()?;
+        }
+        Ok(())
+    }
+}
+
+// .npy file whose number of rows is written into its header once finished, when it is moved
+// from its partial path to its name
+struct NpyWriter {
+    file: BufWriter<File>,
+    path: PathBuf,
+    partial: PathBuf,
+    descr: &'static str,
+    row_shape: Vec<usize>,
+    rows: usize,
+}
+
+impl NpyWriter {
+    fn create(path: &Path, descr: &'static str, row_shape: &[usize]) -> io::Result<NpyWriter> {
+        let partial = partial_path(path);
+        let mut npy = NpyWriter {
+            file: BufWriter::new(File::create(&partial)?),
+            path: path.to_path_buf(),
+            partial,
+            descr,
+            row_shape: row_shape.to_vec(),
+            rows: 0,
+        };
+        npy.write_header()?;
+        Ok(npy)
+    }
+
+    fn write_row(&mut self, row: &[u8]) -> io::Result<()> {
+        self.rows += 1;
+        self.file.write_all(row)
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.file.flush()?;
+        fs::rename(&self.partial, &self.path)
+    }
+
+    // version 1.0 header padded with spaces to NPY_HEADER_SIZE bytes
+    fn write_header(&mut self) -> io::Result<()> {
+        let shape: Vec<String> = std::iter::once(self.rows)
+            .chain(self.row_shape.iter().copied())
+            .map(|n| n.to_string())
+            .collect();
+        let shape = match shape.len() {
+            1 => format!("({},)", shape[0]),
+            _ => format!("({})", shape.join(", ")),
+        };
+        let dict = format!(
+            "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+            self.descr, shape
+        );
+        let mut header = b"\x93NUMPY\x01\x00".to_vec();
+        header.extend_from_slice(&((NPY_HEADER_SIZE - 10) as u16).to_le_bytes());
+        header.extend_from_slice(dict.as_bytes());
+        header.resize(NPY_HEADER_SIZE - 1, b' ');
+        header.push(b'\n');
+        self.file.write_all(&header)
+    }
+}
+
+// shape and bytes of a range of rows of an .npy file with the given dtype
+fn read_rows(
+    path: &Path,
+    descr: &str,
+    rows: std::ops::Range<usize>,
+) -> io::Result<(Vec<usize>, Vec<u8>)> {
+    let mut file = BufReader::new(File::open(path)?);
+    let bad = || invalid(format!("{} is not a supported npy file", path.display()));
+    let mut magic = [0u8; 8];
+    file.read_exact(&mut magic)?;
+    let header_len = match &magic {
+        b"\x93NUMPY\x01\x00" => {
+            let mut len = [0u8; 2];
+            file.read_exact(&mut len)?;
+            u16::from_le_bytes(len) as usize
+        }
+        b"\x93NUMPY\x02\x00" | b"\x93NUMPY\x03\x00" => {
+            let mut len = [0u8; 4];
+            file.read_exact(&mut len)?;
+            u32::from_le_bytes(len) as usize
+        }
+        _ => return Err(bad()),
+    };
+    let mut header = vec![0u8; header_len];
+    file.read_exact(&mut header)?;
+    let header = String::from_utf8(header).map_err(|_| bad())?;
+    let value = |key: &str| {
+        let start = header.find(&format!("'{}': ", key))? + key.len() + 4;
+        Some(&header[start..])
+    };
+    let file_descr = value("descr").and_then(|v| v.strip_prefix('\'')?.split('\'').next());
+    if file_descr != Some(descr) || value("fortran_order").is_none_or(|v| !v.starts_with("False")) {
+        return Err(bad());
+    }
+    let shape = value("shape")
+        .and_then(|v| v.strip_prefix('(')?.split(')').next())
+        .ok_or_else(bad)?;
+    let shape: Vec<usize> = shape
+        .split(',')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(|n| n.parse().map_err(|_| bad()))
+        .collect::<io::Result<_>>()?;
+    let (&num_rows, row_shape) = shape.split_first().ok_or_else(bad)?;
+    if rows.end > num_rows {
+        return Err(invalid(format!("{} has too few rows", path.display())));
+    }
+
+    let item_size = if descr.ends_with('4') { 4 } else { 1 };
+    let row_size = item_size * row_shape.iter().product::<usize>();
+    file.seek_relative((rows.start * row_size) as i64)?;
+    let mut data = vec![0u8; rows.len() * row_size];
+    file.read_exact(&mut data)?;
+    Ok((row_shape.to_vec(), data))
+}
+
+// <path>.partial
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    PathBuf::from(partial)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn npy_header_round_trip() {
+        let dir = tempdir::TempDir::new("ale-env-npy").unwrap();
+        let path = dir.path().join("observation.npy");
+        let mut npy = NpyWriter::create(&path, "|u1", &[2, 3]).unwrap();
+        for row in 0..4u8 {
+            npy.write_row(&[row; 6]).unwrap();
+        }
+        assert!(!path.exists());
+        npy.finish().unwrap();
+
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data.len(), NPY_HEADER_SIZE + 4 * 6);
+        assert_eq!(data[NPY_HEADER_SIZE - 1], b'\n');
+        let (shape, rows) = read_rows(&path, "|u1", 1..3).unwrap();
+        assert_eq!(shape, [2, 3]);
+        assert_eq!(rows, [[1u8; 6], [2; 6]].concat());
+        assert!(read_rows(&path, "<i4", 0..1).is_err());
+        assert!(read_rows(&path, "|u1", 3..5).is_err());
+
+        // one dimensional arrays have a trailing comma in their shape
+        let path = dir.path().join("reward.npy");
+        let mut npy = NpyWriter::create(&path, "<f4", &[]).unwrap();
+        npy.write_row(&1.5f32.to_le_bytes()).unwrap();
+        npy.finish().unwrap();
+        let (shape, rows) = read_rows(&path, "<f4", 0..1).unwrap();
+        assert!(shape.is_empty());
+        assert_eq!(rows, 1.5f32.to_le_bytes());
+    }
+}
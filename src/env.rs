@@ -0,0 +1,151 @@
+//! Traits abstracting over single and vectorized environments, so that agents can be written
+//! once against `Atari`, `VecAtari` or a mock.
+
+use crate::action::Action;
+use crate::atari::Atari;
+use crate::seeding::derive_seed;
+use crate::vec_atari::VecAtari;
+
+/// Outcome of a single environment step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transition<O> {
+    pub obs: O,
+    pub reward: f32,
+    pub terminal: bool,
+    pub truncation: bool,
+}
+
+impl<O> Transition<O> {
+    pub fn done(&self) -> bool {
+        self.terminal || self.truncation
+    }
+}
+
+pub trait Environment {
+    type Observation;
+    type Action;
+
+    /// Starts a new episode, reseeding the environment if a seed is given.
+    fn reset(&mut self, seed: Option<i32>) -> Self::Observation;
+
+    fn step(&mut self, action: Self::Action) -> Transition<Self::Observation>;
+
+    /// Actions accepted by `step`.
+    fn action_space(&self) -> Vec<Self::Action>;
+}
+
+/// A batch of environments stepped together.
+///
+/// Environments whose episode ends are reset within the same `step` call: the returned
+/// observation is the first one of the next episode, while `reward`, `terminal` and
+/// `truncation` still describe the step that ended the previous one.
+pub trait VectorEnvironment {
+    type Observation;
+    type Action;
+
+    fn num_envs(&self) -> usize;
+
+    /// Resets every environment; a seed is spread over them with `derive_seed`.
+    fn reset(&mut self, seed: Option<i32>) -> Vec<Self::Observation>;
+
+    /// Steps environment `i` with `actions[i]`.
+    fn step(&mut self, actions: &[Self::Action]) -> Vec<Transition<Self::Observation>>;
+
+    /// Actions accepted by each environment.
+    fn action_space(&self) -> Vec<Self::Action>;
+}
+
+impl Environment for Atari {
+    type Observation = Vec<u8>;
+    type Action = Action;
+
+    fn reset(&mut self, seed: Option<i32>) -> Vec<u8> {
+        Atari::reset(self, seed);
+        self.obs()
+    }
+
+    fn step(&mut self, action: Action) -> Transition<Vec<u8>> {
+        let (reward, terminal, truncation, _) = Atari::step(self, action);
+        Transition {
+            obs: self.obs(),
+            reward: reward as f32,
+            terminal,
+            truncation,
+        }
+    }
+
+    fn action_space(&self) -> Vec<Action> {
+        self.get_action_set()
+    }
+}
+
+impl VectorEnvironment for VecAtari {
+    type Observation = Vec<u8>;
+    type Action = Action;
+
+    fn num_envs(&self) -> usize {
+        VecAtari::num_envs(self)
+    }
+
+    fn reset(&mut self, seed: Option<i32>) -> Vec<Vec<u8>> {
+        VecAtari::reset(self, seed)
+            .into_iter()
+            .map(|result| result.obs)
+            .collect()
+    }
+
+    fn step(&mut self, actions: &[Action]) -> Vec<Transition<Vec<u8>>> {
+        VecAtari::step(self, actions.to_vec())
+            .into_iter()
+            .map(|result| Transition {
+                obs: result.obs,
+                reward: result.reward as f32,
+                terminal: result.terminal,
+                truncation: result.truncation,
+            })
+            .collect()
+    }
+
+    fn action_space(&self) -> Vec<Action> {
+        VecAtari::action_space(self).to_vec()
+    }
+}
+
+/// Steps a list of environments one after the other on the calling thread, e.g. mocks in tests.
+impl<E: Environment> VectorEnvironment for Vec<E>
+where
+    E::Action: Clone,
+{
+    type Observation = E::Observation;
+    type Action = E::Action;
+
+    fn num_envs(&self) -> usize {
+        self.len()
+    }
+
+    fn reset(&mut self, seed: Option<i32>) -> Vec<E::Observation> {
+        self.iter_mut()
+            .enumerate()
+            .map(|(i, env)| env.reset(seed.map(|seed| derive_seed(seed, i))))
+            .collect()
+    }
+
+    fn step(&mut self, actions: &[E::Action]) -> Vec<Transition<E::Observation>> {
+        self.iter_mut()
+            .zip(actions)
+            .map(|(env, action)| {
+                let mut transition = env.step(action.clone());
+                if transition.done() {
+                    transition.obs = env.reset(None);
+                }
+                transition
+            })
+            .collect()
+    }
+
+    fn action_space(&self) -> Vec<E::Action> {
+        self.first()
+            .map(|env| env.action_space())
+            .unwrap_or_default()
+    }
+}
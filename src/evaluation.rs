@@ -0,0 +1,308 @@
+//! Evaluation runner playing a policy under a standard protocol, so that scores are comparable
+//! across experiments.
+//!
+//! Episode `i` runs on a fresh `Atari` seeded with `derive_seed(seed, i)`, so that with an
+//! episode budget the report does not depend on the number of threads.
+//!
+//! Human starts are read from a state file holding the states of `Atari::clone_state`, each
+//! written as its length in bytes (`u32`, little endian) followed by the encoded state.
+
+use crate::action::Action;
+use crate::atari::Atari;
+use crate::config::AleConfig;
+use crate::metrics::Normalization;
+use crate::rom::{BundledRom, ParseRomError};
+use crate::seeding::derive_seed;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+
+/// How each evaluation episode starts.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Start {
+    Reset,
+    /// A uniform number of no-op actions in `[0, max]` before the policy takes over
+    /// (Mnih et al., 2015).
+    Noops(u32),
+    /// Episode `i` starts from state `i % states.len()` (Nair et al., 2015).
+    HumanStarts(Vec<Vec<u8>>),
+}
+
+/// When the evaluation stops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Budget {
+    Episodes(usize),
+    /// Total emulator frames over all episodes; episodes still running when the budget runs
+    /// out are dropped from the report.
+    Frames(u64),
+}
+
+#[derive(Clone, Debug)]
+pub struct Protocol {
+    pub start: Start,
+    pub budget: Budget,
+    /// Truncation of each episode in emulator frames, 108 000 frames are 30 minutes of play.
+    pub max_frames: u32,
+    pub gray_scale: bool,
+    /// ALE settings, e.g. `repeat_action_probability` for sticky actions.
+    pub config: AleConfig,
+}
+
+impl Protocol {
+    /// Up to 30 no-op starts without sticky actions.
+    pub fn noop_starts(episodes: usize) -> Protocol {
+        Protocol {
+            start: Start::Noops(30),
+            budget: Budget::Episodes(episodes),
+            max_frames: 108_000,
+            gray_scale: true,
+            config: AleConfig::default(),
+        }
+    }
+
+    /// Sticky actions with probability 0.25 and plain resets (Machado et al., 2018).
+    pub fn sticky_actions(episodes: usize) -> Protocol {
+        let mut config = AleConfig::default();
+        config.repeat_action_probability = 0.25;
+        Protocol {
+            start: Start::Reset,
+            config,
+            ..Protocol::noop_starts(episodes)
+        }
+    }
+
+    /// Starts from the states of a human starts file, see `load_states`.
+    pub fn human_starts(states: Vec<Vec<u8>>, episodes: usize) -> Protocol {
+        Protocol {
+            start: Start::HumanStarts(states),
+            ..Protocol::noop_starts(episodes)
+        }
+    }
+}
+
+/// Outcome of one evaluation episode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpisodeResult {
+    pub index: usize,
+    pub seed: i32,
+    /// No-ops played before the policy took over.
+    pub noops: u32,
+    /// Index of the human start the episode began from.
+    pub start_state: Option<usize>,
+    pub score: i32,
+    pub steps: i32,
+    pub frames: i32,
+    pub truncation: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct EvaluationReport {
+    pub game: BundledRom,
+    /// Completed episodes, ordered by index.
+    pub episodes: Vec<EpisodeResult>,
+}
+
+impl EvaluationReport {
+    pub fn returns(&self) -> Vec<f64> {
+        self.episodes.iter().map(|e| e.score as f64).collect()
+    }
+
+    pub fn mean_return(&self) -> f64 {
+        self.returns().iter().sum::<f64>() / self.episodes.len() as f64
+    }
+
+    /// Mean return normalized against the reference scores of the game, if it has any.
+    pub fn normalized_score(&self, normalization: Normalization) -> Option<f64> {
+        normalization.apply(self.game, self.mean_return())
+    }
+}
+
+/// Plays `policy` on `game` under `protocol` with `num_threads` threads.
+///
+/// The policy maps an observation, laid out as `Atari::obs`, to an action; it is shared by
+/// all threads. Unknown games, invalid configs and human starts that do not decode are
+/// returned as errors and stop the evaluation; a panic of the policy or of an env stops it
+/// too and is raised again here.
+pub fn evaluate<F>(
+    game: &str,
+    protocol: &Protocol,
+    policy: F,
+    seed: i32,
+    num_threads: usize,
+) -> Result<EvaluationReport, String>
+where
+    F: Fn(&[u8]) -> Action + Sync,
+{
+    let rom: BundledRom = game.parse().map_err(|e: ParseRomError| e.to_string())?;
+    if num_threads == 0 {
+        return Err("Evaluation needs at least one thread".to_string());
+    }
+    if let Start::HumanStarts(states) = &protocol.start {
+        if states.is_empty() {
+            return Err("Human starts need at least one state".to_string());
+        }
+    }
+    let next_episode = AtomicUsize::new(0);
+    let frames_used = AtomicU64::new(0);
+    // set when a thread failed or panicked, so that the others stop early
+    let failed = AtomicBool::new(false);
+
+    let play = || {
+        let mut results = Vec::new();
+        while !failed.load(Ordering::Relaxed) {
+            let index = next_episode.fetch_add(1, Ordering::Relaxed);
+            let budget_left = match protocol.budget {
+                Budget::Episodes(episodes) => index < episodes,
+                Budget::Frames(frames) => frames_used.load(Ordering::Relaxed) < frames,
+            };
+            if !budget_left {
+                break;
+            }
+            let seed = derive_seed(seed, index);
+            let episode = panic::catch_unwind(AssertUnwindSafe(|| {
+                run_episode(rom, protocol, &policy, index, seed, &frames_used)
+            }));
+            match episode {
+                Ok(Ok(result)) => results.extend(result),
+                Ok(Err(e)) => {
+                    failed.store(true, Ordering::Relaxed);
+                    return Err(e);
+                }
+                Err(payload) => {
+                    failed.store(true, Ordering::Relaxed);
+                    panic::resume_unwind(payload);
+                }
+            }
+        }
+        Ok(results)
+    };
+    let joined: Vec<_> = thread::scope(|scope| {
+        let threads: Vec<_> = (0..num_threads).map(|_| scope.spawn(play)).collect();
+        threads.into_iter().map(|thread| thread.join()).collect()
+    });
+
+    let mut episodes = Vec::new();
+    for results in joined {
+        match results {
+            Ok(results) => episodes.extend(results?),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+    episodes.sort_by_key(|e: &EpisodeResult| e.index);
+    if let Budget::Episodes(n) = protocol.budget {
+        assert_eq!(episodes.len(), n, "Evaluation lost episodes");
+    }
+    Ok(EvaluationReport {
+        game: rom,
+        episodes,
+    })
+}
+
+// play one episode, None when the frame budget runs out before it ends; episode frames are
+// counted from the start state, so that human starts get the whole max_frames
+fn run_episode<F>(
+    rom: BundledRom,
+    protocol: &Protocol,
+    policy: &F,
+    index: usize,
+    seed: i32,
+    frames_used: &AtomicU64,
+) -> Result<Option<EpisodeResult>, String>
+where
+    F: Fn(&[u8]) -> Action,
+{
+    // truncation is checked here, relative to the start state
+    let mut env = Atari::try_new(
+        rom.name(),
+        i32::MAX as u32,
+        protocol.gray_scale,
+        Some(seed),
+        None,
+        Some(protocol.config.clone()),
+    )?;
+    let frame_skip = protocol.config.frame_skip as u64;
+    // counts the frames of one step, true once the total goes over the budget
+    let out_of_budget = || match protocol.budget {
+        Budget::Frames(budget) => frames_used.fetch_add(frame_skip, Ordering::Relaxed) >= budget,
+        Budget::Episodes(_) => false,
+    };
+
+    let mut noops = 0;
+    let mut start_state = None;
+    let mut start_frames = 0;
+    match &protocol.start {
+        Start::Reset => (),
+        Start::Noops(max_noops) => {
+            noops = StdRng::seed_from_u64(seed as u64).gen_range(0..=*max_noops);
+            for _ in 0..noops {
+                let (_, terminal, _, _) = env.step(Action::Noop);
+                if out_of_budget() {
+                    return Ok(None);
+                }
+                if terminal || env.episode_frame_number() >= protocol.max_frames as i32 {
+                    env.reset(None);
+                }
+            }
+        }
+        Start::HumanStarts(states) => {
+            let i = index % states.len();
+            env.restore_state(&states[i])
+                .map_err(|e| format!("Human start {}: {}", i, e))?;
+            start_state = Some(i);
+            start_frames = env.episode_frame_number();
+        }
+    }
+
+    loop {
+        let action = policy(&env.obs());
+        let (_, terminal, _, _) = env.step(action);
+        if out_of_budget() {
+            return Ok(None);
+        }
+        let frames = env.episode_frame_number() - start_frames;
+        let truncation = frames >= protocol.max_frames as i32;
+        if terminal || truncation {
+            return Ok(Some(EpisodeResult {
+                index,
+                seed,
+                noops,
+                start_state,
+                score: env.get_score(),
+                steps: env.episode_steps(),
+                frames,
+                truncation: !terminal,
+            }));
+        }
+    }
+}
+
+/// Reads a human starts file.
+pub fn load_states<P: AsRef<Path>>(path: P) -> io::Result<Vec<Vec<u8>>> {
+    let data = fs::read(path)?;
+    let mut states = Vec::new();
+    let mut rest = &data[..];
+    while !rest.is_empty() {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Truncated state file");
+        let (len, tail) = rest.split_at_checked(4).ok_or_else(invalid)?;
+        let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+        let (state, tail) = tail.split_at_checked(len).ok_or_else(invalid)?;
+        states.push(state.to_vec());
+        rest = tail;
+    }
+    Ok(states)
+}
+
+/// Writes states of `Atari::clone_state` as a human starts file.
+pub fn save_states<P: AsRef<Path>>(path: P, states: &[Vec<u8>]) -> io::Result<()> {
+    let mut data = Vec::new();
+    for state in states {
+        data.extend_from_slice(&(state.len() as u32).to_le_bytes());
+        data.extend_from_slice(state);
+    }
+    fs::write(path, data)
+}
@@ -1,14 +1,57 @@
+//! Rust wrapper of the Arcade Learning Environment.
+//!
+//! The Python extension module is built on top of the same API with the `python` feature.
+
+mod action;
+mod action_log;
 mod atari;
 mod bindings;
+mod config;
+mod dataset;
+mod env;
+mod evaluation;
+mod logger;
+mod metadata;
+mod metrics;
+#[cfg(unix)]
+mod obs_ring;
+#[cfg(unix)]
+mod process_vec_atari;
+#[cfg(feature = "python")]
+mod python;
+mod replay;
+mod rom;
+mod seeding;
+mod suites;
+mod trajectory;
 mod vec_atari;
-use pyo3::prelude::*;
-
-
+mod video;
 
-#[pymodule]
-mod ale_env {
-    #[pymodule_export]
-    use super::atari::Atari;
-    #[pymodule_export]
-    use super::vec_atari::VecAtari;
-}
\ No newline at end of file
+pub use action::{Action, Direction};
+pub use action_log::{ActionLog, LogHeader, ReplayMismatch};
+pub use atari::Atari;
+pub use config::{AleConfig, SettingValue};
+pub use dataset::{export_dataset, Dataset, DatasetOptions, DatasetWriter, EpisodeEntry};
+pub use env::{Environment, Transition, VectorEnvironment};
+pub use evaluation::{
+    evaluate, load_states, save_states, Budget, EpisodeResult, EvaluationReport, Protocol, Start,
+};
+pub use logger::{logger_mode, set_logger_mode, LoggerMode};
+pub use metadata::{game_infos, GameInfo};
+pub use metrics::{
+    bootstrap_ci, capped_human_normalized_score, human_normalized_score, record_normalized_score,
+    Aggregate, Interval, Normalization,
+};
+#[cfg(unix)]
+pub use obs_ring::ObsRing;
+#[cfg(unix)]
+pub use process_vec_atari::{
+    run_worker_if_spawned, set_worker_command, worker_main, ProcessVecAtari, REPLY_TIMEOUT,
+};
+pub use replay::{Batch, ReplayBuffer, ReplayConfig};
+pub use rom::{BundledRom, ParseRomError};
+pub use seeding::derive_seed;
+pub use suites::Suite;
+pub use trajectory::{EpisodeStart, RecordingOptions, Trajectory};
+pub use vec_atari::{EnvError, EnvSpec, StepResult, VecAtari, WorkerConfig};
+pub use video::{trajectory_video, FrameInfo, VideoFormat, VideoOptions, VideoWriter};
@@ -0,0 +1,59 @@
+//! Verbosity of ALE's logger.
+//!
+//! ALE keeps a single, process-wide log level and writes its messages straight to stderr from
+//! C++, so they cannot be intercepted here; this module only controls which of them get printed.
+
+use crate::bindings::root::setLoggerMode;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+// -1 until a mode has been applied to ALE
+static LOGGER_MODE: AtomicI32 = AtomicI32::new(-1);
+
+#[cfg_attr(feature = "python", pyclass(eq, eq_int))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoggerMode {
+    Info = 0,
+    Warning = 1,
+    Error = 2,
+}
+
+impl LoggerMode {
+    fn from_int(mode: i32) -> Option<LoggerMode> {
+        match mode {
+            0 => Some(LoggerMode::Info),
+            1 => Some(LoggerMode::Warning),
+            2 => Some(LoggerMode::Error),
+            _ => None,
+        }
+    }
+
+    /// Maps a Python `logging` level to the closest ALE mode, e.g. `logging.WARNING` to `Warning`.
+    pub fn from_logging_level(level: i32) -> LoggerMode {
+        match level {
+            i32::MIN..=20 => LoggerMode::Info,
+            21..=30 => LoggerMode::Warning,
+            _ => LoggerMode::Error,
+        }
+    }
+}
+
+/// Sets the verbosity of ALE's logger for the whole process.
+pub fn set_logger_mode(mode: LoggerMode) {
+    unsafe { setLoggerMode(mode as c_int) };
+    LOGGER_MODE.store(mode as i32, Ordering::SeqCst);
+}
+
+/// Returns the current verbosity of ALE's logger. Until a mode is set, envs only log errors.
+pub fn logger_mode() -> LoggerMode {
+    LoggerMode::from_int(LOGGER_MODE.load(Ordering::SeqCst)).unwrap_or(LoggerMode::Error)
+}
+
+// apply the default mode unless the user already chose one
+pub(crate) fn init_logger_mode() {
+    if LOGGER_MODE.load(Ordering::SeqCst) < 0 {
+        set_logger_mode(LoggerMode::Error);
+    }
+}
@@ -0,0 +1,615 @@
+//! Per-game facts that do not require loading the ROM.
+//!
+//! The table covers the 57 games of the standard Atari benchmark. Action counts, modes and
+//! difficulties mirror ALE's game definitions (`Atari::available_modes` remains authoritative),
+//! random and human reference scores are the ones published with DQN (Mnih et al., 2015) and
+//! used by Agent57 (Badia et al., 2020), human world records are taken from Toromanoff et al.
+//! (2019).
+
+use crate::rom::BundledRom;
+use crate::rom::BundledRom::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameInfo {
+    pub rom: BundledRom,
+    /// Size of the minimal action set.
+    pub num_actions: usize,
+    pub modes: &'static [i32],
+    pub difficulties: &'static [i32],
+    /// FIRE has to be pressed to start play after a reset or a lost life, e.g. Breakout's serve.
+    pub needs_fire: bool,
+    /// Average score of a uniformly random policy.
+    pub random_score: f64,
+    /// Average score of a professional human game tester.
+    pub human_score: f64,
+    /// Human world record.
+    pub record_score: f64,
+}
+
+static GAME_INFO: [GameInfo; 57] = [
+    GameInfo {
+        rom: Alien,
+        num_actions: 18,
+        modes: &[0, 1, 2, 3],
+        difficulties: &[0, 1, 2, 3],
+        needs_fire: false,
+        random_score: 227.8,
+        human_score: 7127.7,
+        record_score: 251916.0,
+    },
+    GameInfo {
+        rom: Amidar,
+        num_actions: 10,
+        modes: &[0],
+        difficulties: &[0, 3],
+        needs_fire: false,
+        random_score: 5.8,
+        human_score: 1719.5,
+        record_score: 104159.0,
+    },
+    GameInfo {
+        rom: Assault,
+        num_actions: 7,
+        modes: &[0],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 222.4,
+        human_score: 742.0,
+        record_score: 8647.0,
+    },
+    GameInfo {
+        rom: Asterix,
+        num_actions: 9,
+        modes: &[0],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 210.0,
+        human_score: 8503.3,
+        record_score: 1000000.0,
+    },
+    GameInfo {
+        rom: Asteroids,
+        num_actions: 14,
+        modes: &[
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
+            24, 25, 26, 27, 28, 29, 30, 31, 128,
+        ],
+        difficulties: &[0, 3],
+        needs_fire: false,
+        random_score: 719.1,
+        human_score: 47388.7,
+        record_score: 10506650.0,
+    },
+    GameInfo {
+        rom: Atlantis,
+        num_actions: 4,
+        modes: &[0, 1, 2, 3],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 12850.0,
+        human_score: 29028.1,
+        record_score: 10604840.0,
+    },
+    GameInfo {
+        rom: BankHeist,
+        num_actions: 18,
+        modes: &[0, 4, 8, 12, 16, 20, 24, 28],
+        difficulties: &[0, 1, 2, 3],
+        needs_fire: false,
+        random_score: 14.2,
+        human_score: 753.1,
+        record_score: 82058.0,
+    },
+    GameInfo {
+        rom: BattleZone,
+        num_actions: 18,
+        modes: &[1, 2, 3],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 2360.0,
+        human_score: 37187.5,
+        record_score: 801000.0,
+    },
+    GameInfo {
+        rom: BeamRider,
+        num_actions: 9,
+        modes: &[0],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 363.9,
+        human_score: 16926.5,
+        record_score: 999999.0,
+    },
+    GameInfo {
+        rom: Berzerk,
+        num_actions: 18,
+        modes: &[1, 2, 3, 4, 5, 6, 7, 8, 9, 16, 17, 18],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 123.7,
+        human_score: 2630.4,
+        record_score: 1057940.0,
+    },
+    GameInfo {
+        rom: Bowling,
+        num_actions: 6,
+        modes: &[0, 2, 4],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 23.1,
+        human_score: 160.7,
+        record_score: 300.0,
+    },
+    GameInfo {
+        rom: Boxing,
+        num_actions: 18,
+        modes: &[0],
+        difficulties: &[0, 1, 2, 3],
+        needs_fire: false,
+        random_score: 0.1,
+        human_score: 12.1,
+        record_score: 100.0,
+    },
+    GameInfo {
+        rom: Breakout,
+        num_actions: 4,
+        modes: &[0, 4, 8, 12, 16, 20, 24, 28, 32, 36, 40, 44],
+        difficulties: &[0, 1],
+        needs_fire: true,
+        random_score: 1.7,
+        human_score: 30.5,
+        record_score: 864.0,
+    },
+    GameInfo {
+        rom: Centipede,
+        num_actions: 18,
+        modes: &[22, 86],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 2090.9,
+        human_score: 12017.0,
+        record_score: 1301709.0,
+    },
+    GameInfo {
+        rom: ChopperCommand,
+        num_actions: 18,
+        modes: &[0, 2],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 811.0,
+        human_score: 7387.8,
+        record_score: 999999.0,
+    },
+    GameInfo {
+        rom: CrazyClimber,
+        num_actions: 9,
+        modes: &[0, 1, 2, 3],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 10780.5,
+        human_score: 35829.4,
+        record_score: 219900.0,
+    },
+    GameInfo {
+        rom: Defender,
+        num_actions: 18,
+        modes: &[1, 2, 3, 4, 5, 6, 7, 8, 9, 16],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 2874.5,
+        human_score: 18688.9,
+        record_score: 6010500.0,
+    },
+    GameInfo {
+        rom: DemonAttack,
+        num_actions: 6,
+        modes: &[1, 3, 5, 7],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 152.1,
+        human_score: 1971.0,
+        record_score: 1556345.0,
+    },
+    GameInfo {
+        rom: DoubleDunk,
+        num_actions: 18,
+        modes: &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: -18.6,
+        human_score: -16.4,
+        record_score: 21.0,
+    },
+    GameInfo {
+        rom: Enduro,
+        num_actions: 9,
+        modes: &[0],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 0.0,
+        human_score: 860.5,
+        record_score: 9500.0,
+    },
+    GameInfo {
+        rom: FishingDerby,
+        num_actions: 18,
+        modes: &[0],
+        difficulties: &[0, 1, 2, 3],
+        needs_fire: false,
+        random_score: -91.7,
+        human_score: -38.7,
+        record_score: 71.0,
+    },
+    GameInfo {
+        rom: Freeway,
+        num_actions: 3,
+        modes: &[0, 1, 2, 3, 4, 5, 6, 7],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 0.0,
+        human_score: 29.6,
+        record_score: 38.0,
+    },
+    GameInfo {
+        rom: Frostbite,
+        num_actions: 18,
+        modes: &[0, 2],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 65.2,
+        human_score: 4334.7,
+        record_score: 454830.0,
+    },
+    GameInfo {
+        rom: Gopher,
+        num_actions: 8,
+        modes: &[0, 2],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 257.6,
+        human_score: 2412.5,
+        record_score: 355040.0,
+    },
+    GameInfo {
+        rom: Gravitar,
+        num_actions: 18,
+        modes: &[0, 1, 2, 3, 4],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 173.0,
+        human_score: 3351.4,
+        record_score: 162850.0,
+    },
+    GameInfo {
+        rom: Hero,
+        num_actions: 18,
+        modes: &[0, 1, 2, 3, 4],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 1027.0,
+        human_score: 30826.4,
+        record_score: 1000000.0,
+    },
+    GameInfo {
+        rom: IceHockey,
+        num_actions: 18,
+        modes: &[0, 2],
+        difficulties: &[0, 1, 2, 3],
+        needs_fire: false,
+        random_score: -11.2,
+        human_score: 0.9,
+        record_score: 36.0,
+    },
+    GameInfo {
+        rom: Jamesbond,
+        num_actions: 18,
+        modes: &[0, 1],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 29.0,
+        human_score: 302.8,
+        record_score: 45550.0,
+    },
+    GameInfo {
+        rom: Kangaroo,
+        num_actions: 18,
+        modes: &[0],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 52.0,
+        human_score: 3035.0,
+        record_score: 1424600.0,
+    },
+    GameInfo {
+        rom: Krull,
+        num_actions: 18,
+        modes: &[0],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 1598.0,
+        human_score: 2665.5,
+        record_score: 104100.0,
+    },
+    GameInfo {
+        rom: KungFuMaster,
+        num_actions: 14,
+        modes: &[0],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 258.5,
+        human_score: 22736.3,
+        record_score: 1000000.0,
+    },
+    GameInfo {
+        rom: MontezumaRevenge,
+        num_actions: 18,
+        modes: &[0],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 0.0,
+        human_score: 4753.3,
+        record_score: 1219200.0,
+    },
+    GameInfo {
+        rom: MsPacman,
+        num_actions: 9,
+        modes: &[0, 1, 2, 3],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 307.3,
+        human_score: 6951.6,
+        record_score: 290090.0,
+    },
+    GameInfo {
+        rom: NameThisGame,
+        num_actions: 6,
+        modes: &[8, 24, 40],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 2292.3,
+        human_score: 8049.0,
+        record_score: 25220.0,
+    },
+    GameInfo {
+        rom: Phoenix,
+        num_actions: 8,
+        modes: &[0],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 761.4,
+        human_score: 7242.6,
+        record_score: 4014440.0,
+    },
+    GameInfo {
+        rom: Pitfall,
+        num_actions: 18,
+        modes: &[0],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: -229.4,
+        human_score: 6463.7,
+        record_score: 114000.0,
+    },
+    GameInfo {
+        rom: Pong,
+        num_actions: 6,
+        modes: &[0, 1],
+        difficulties: &[0, 1, 2, 3],
+        needs_fire: false,
+        random_score: -20.7,
+        human_score: 14.6,
+        record_score: 21.0,
+    },
+    GameInfo {
+        rom: PrivateEye,
+        num_actions: 18,
+        modes: &[0, 1, 2, 3, 4],
+        difficulties: &[0, 1, 2, 3],
+        needs_fire: false,
+        random_score: 24.9,
+        human_score: 69571.3,
+        record_score: 101800.0,
+    },
+    GameInfo {
+        rom: Qbert,
+        num_actions: 6,
+        modes: &[0],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 163.9,
+        human_score: 13455.0,
+        record_score: 2400000.0,
+    },
+    GameInfo {
+        rom: Riverraid,
+        num_actions: 18,
+        modes: &[0],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 1338.5,
+        human_score: 17118.0,
+        record_score: 1000000.0,
+    },
+    GameInfo {
+        rom: RoadRunner,
+        num_actions: 18,
+        modes: &[0],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 11.5,
+        human_score: 7845.0,
+        record_score: 2038100.0,
+    },
+    GameInfo {
+        rom: Robotank,
+        num_actions: 18,
+        modes: &[0],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 2.2,
+        human_score: 11.9,
+        record_score: 76.0,
+    },
+    GameInfo {
+        rom: Seaquest,
+        num_actions: 18,
+        modes: &[0],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 68.4,
+        human_score: 42054.7,
+        record_score: 999999.0,
+    },
+    GameInfo {
+        rom: Skiing,
+        num_actions: 3,
+        modes: &[0],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: -17098.1,
+        human_score: -4336.9,
+        record_score: -3272.0,
+    },
+    GameInfo {
+        rom: Solaris,
+        num_actions: 18,
+        modes: &[0],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 1236.3,
+        human_score: 12326.7,
+        record_score: 111420.0,
+    },
+    GameInfo {
+        rom: SpaceInvaders,
+        num_actions: 6,
+        modes: &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 148.0,
+        human_score: 1668.7,
+        record_score: 621535.0,
+    },
+    GameInfo {
+        rom: StarGunner,
+        num_actions: 18,
+        modes: &[0, 1, 2, 3],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 664.0,
+        human_score: 10250.0,
+        record_score: 77400.0,
+    },
+    GameInfo {
+        rom: Surround,
+        num_actions: 5,
+        modes: &[0, 2],
+        difficulties: &[0, 1, 2, 3],
+        needs_fire: false,
+        random_score: -10.0,
+        human_score: 6.5,
+        record_score: 10.0,
+    },
+    GameInfo {
+        rom: Tennis,
+        num_actions: 18,
+        modes: &[0, 2],
+        difficulties: &[0, 1, 2, 3],
+        needs_fire: false,
+        random_score: -23.8,
+        human_score: -8.3,
+        record_score: 21.0,
+    },
+    GameInfo {
+        rom: TimePilot,
+        num_actions: 10,
+        modes: &[0],
+        difficulties: &[0, 1, 2],
+        needs_fire: false,
+        random_score: 3568.0,
+        human_score: 5229.2,
+        record_score: 65300.0,
+    },
+    GameInfo {
+        rom: Tutankham,
+        num_actions: 8,
+        modes: &[0, 4, 8, 12],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 11.4,
+        human_score: 167.6,
+        record_score: 5384.0,
+    },
+    GameInfo {
+        rom: UpNDown,
+        num_actions: 6,
+        modes: &[0],
+        difficulties: &[0, 1, 2, 3],
+        needs_fire: false,
+        random_score: 533.4,
+        human_score: 11693.2,
+        record_score: 82840.0,
+    },
+    GameInfo {
+        rom: Venture,
+        num_actions: 18,
+        modes: &[0],
+        difficulties: &[0, 1, 2, 3],
+        needs_fire: false,
+        random_score: 0.0,
+        human_score: 1187.5,
+        record_score: 38900.0,
+    },
+    GameInfo {
+        rom: VideoPinball,
+        num_actions: 9,
+        modes: &[0, 2],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 0.0,
+        human_score: 17667.9,
+        record_score: 89218328.0,
+    },
+    GameInfo {
+        rom: WizardOfWor,
+        num_actions: 10,
+        modes: &[0],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 563.5,
+        human_score: 4756.5,
+        record_score: 395300.0,
+    },
+    GameInfo {
+        rom: YarsRevenge,
+        num_actions: 18,
+        modes: &[0, 32, 64, 96],
+        difficulties: &[0, 1],
+        needs_fire: false,
+        random_score: 3092.9,
+        human_score: 54576.9,
+        record_score: 15000105.0,
+    },
+    GameInfo {
+        rom: Zaxxon,
+        num_actions: 18,
+        modes: &[0, 8, 16, 24],
+        difficulties: &[0],
+        needs_fire: false,
+        random_score: 32.5,
+        human_score: 9173.3,
+        record_score: 83700.0,
+    },
+];
+
+impl BundledRom {
+    /// Metadata of the game, `None` for games outside the standard benchmark.
+    pub fn info(&self) -> Option<&'static GameInfo> {
+        GAME_INFO.iter().find(|info| info.rom == *self)
+    }
+}
+
+/// Metadata of every game in the table.
+pub fn game_infos() -> &'static [GameInfo] {
+    &GAME_INFO
+}
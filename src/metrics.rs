@@ -0,0 +1,259 @@
+//! Normalized scores and aggregate metrics over a suite of games.
+//!
+//! Aggregates follow Agarwal et al. (2021): scores are arranged as `scores[game][run]`, the
+//! median and mean are taken over per-game averages, the interquartile mean (IQM) and the
+//! optimality gap over all runs of all games, and confidence intervals come from a stratified
+//! bootstrap that resamples runs independently within each game.
+
+use crate::rom::BundledRom;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Reference points a raw episode return is normalized against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Normalization {
+    /// `(score - random) / (human - random)`.
+    Human,
+    /// `(score - random) / (record - random)`, against the human world record.
+    Record,
+    /// Human normalized score clipped at 1.
+    CappedHuman,
+}
+
+impl Normalization {
+    /// Normalizes a raw return, `None` for games without reference scores.
+    pub fn apply(&self, rom: BundledRom, score: f64) -> Option<f64> {
+        let info = rom.info()?;
+        let normalized = match self {
+            Normalization::Human | Normalization::CappedHuman => {
+                (score - info.random_score) / (info.human_score - info.random_score)
+            }
+            Normalization::Record => {
+                (score - info.random_score) / (info.record_score - info.random_score)
+            }
+        };
+        match self {
+            Normalization::CappedHuman => Some(normalized.min(1.0)),
+            _ => Some(normalized),
+        }
+    }
+
+    /// Normalizes the returns of several runs per game into a `scores[game][run]` matrix.
+    pub fn apply_all(&self, returns: &[(BundledRom, Vec<f64>)]) -> Option<Vec<Vec<f64>>> {
+        returns
+            .iter()
+            .map(|(rom, runs)| runs.iter().map(|&score| self.apply(*rom, score)).collect())
+            .collect()
+    }
+}
+
+pub fn human_normalized_score(rom: BundledRom, score: f64) -> Option<f64> {
+    Normalization::Human.apply(rom, score)
+}
+
+pub fn record_normalized_score(rom: BundledRom, score: f64) -> Option<f64> {
+    Normalization::Record.apply(rom, score)
+}
+
+pub fn capped_human_normalized_score(rom: BundledRom, score: f64) -> Option<f64> {
+    Normalization::CappedHuman.apply(rom, score)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aggregate {
+    Median,
+    Mean,
+    Iqm,
+    /// Average shortfall below a normalized score of 1, lower is better.
+    OptimalityGap,
+}
+
+impl Aggregate {
+    pub const ALL: [Aggregate; 4] = [
+        Aggregate::Median,
+        Aggregate::Mean,
+        Aggregate::Iqm,
+        Aggregate::OptimalityGap,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregate::Median => "median",
+            Aggregate::Mean => "mean",
+            Aggregate::Iqm => "iqm",
+            Aggregate::OptimalityGap => "optimality_gap",
+        }
+    }
+
+    /// Computes the metric over a `scores[game][run]` matrix of normalized scores.
+    pub fn compute(&self, scores: &[Vec<f64>]) -> f64 {
+        let game_means = || scores.iter().map(|runs| mean(runs)).collect::<Vec<f64>>();
+        let pooled = || scores.iter().flatten().copied().collect::<Vec<f64>>();
+        match self {
+            Aggregate::Median => median(game_means()),
+            Aggregate::Mean => mean(&game_means()),
+            Aggregate::Iqm => interquartile_mean(pooled()),
+            Aggregate::OptimalityGap => {
+                1.0 - mean(
+                    &pooled()
+                        .into_iter()
+                        .map(|x| x.min(1.0))
+                        .collect::<Vec<f64>>(),
+                )
+            }
+        }
+    }
+}
+
+/// Point estimate of a metric with its bootstrap confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+    pub estimate: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// Stratified bootstrap confidence interval of `aggregate` at level `confidence`, e.g. 0.95.
+/// Levels outside `(0, 1)`, no bootstrap repetitions and games without runs are errors, since
+/// they have no interval.
+pub fn bootstrap_ci(
+    scores: &[Vec<f64>],
+    aggregate: Aggregate,
+    reps: usize,
+    confidence: f64,
+    seed: u64,
+) -> Result<Interval, String> {
+    if !(confidence > 0.0 && confidence < 1.0) {
+        return Err(format!("confidence must be in (0, 1), got {}", confidence));
+    }
+    if reps == 0 {
+        return Err("The bootstrap needs at least one repetition".to_string());
+    }
+    if scores.is_empty() {
+        return Err("No games to aggregate".to_string());
+    }
+    if let Some(game) = scores.iter().position(|runs| runs.is_empty()) {
+        return Err(format!("Game {} has no runs", game));
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut estimates: Vec<f64> = (0..reps)
+        .map(|_| {
+            let sample: Vec<Vec<f64>> = scores
+                .iter()
+                .map(|runs| {
+                    (0..runs.len())
+                        .map(|_| runs[rng.gen_range(0..runs.len())])
+                        .collect()
+                })
+                .collect();
+            aggregate.compute(&sample)
+        })
+        .collect();
+    estimates.sort_by(|a, b| a.total_cmp(b));
+    let alpha = (1.0 - confidence) / 2.0;
+    Ok(Interval {
+        estimate: aggregate.compute(scores),
+        lower: percentile(&estimates, alpha),
+        upper: percentile(&estimates, 1.0 - alpha),
+    })
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let n = values.len();
+    if n == 0 {
+        return f64::NAN;
+    }
+    match n % 2 {
+        0 => (values[n / 2 - 1] + values[n / 2]) / 2.0,
+        _ => values[n / 2],
+    }
+}
+
+// mean of the middle 50%, dropping a quarter of the values at each end
+fn interquartile_mean(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let cut = values.len() / 4;
+    mean(&values[cut..values.len() - cut])
+}
+
+// linear interpolation between closest ranks of sorted values
+fn percentile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let rank = q * (sorted.len() - 1) as f64;
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_and_mean_average_runs_per_game() {
+        let scores = [vec![1.0, 3.0], vec![5.0], vec![0.0, 1.0, 2.0], vec![10.0]];
+        // per-game averages 2, 5, 1 and 10
+        assert_eq!(Aggregate::Median.compute(&scores), 3.5);
+        assert_eq!(Aggregate::Mean.compute(&scores), 4.5);
+        assert_eq!(Aggregate::Median.compute(&scores[..3]), 2.0);
+    }
+
+    #[test]
+    fn iqm_and_optimality_gap_pool_all_runs() {
+        let scores = [vec![0.0, 8.0, 1.0], vec![2.0, 100.0], vec![3.0, 0.5, 4.0]];
+        // the pooled runs sorted are 0, 0.5, 1, 2, 3, 4, 8, 100 and lose two at each end
+        assert_eq!(Aggregate::Iqm.compute(&scores), 2.5);
+        // runs count at most 1, so the gap is the mean shortfall of 0 and 0.5
+        assert_eq!(Aggregate::OptimalityGap.compute(&scores), 1.5 / 8.0);
+    }
+
+    #[test]
+    fn bootstrap_intervals_contain_the_estimate() {
+        let scores = [
+            vec![0.1, 0.5, 0.9, 0.3],
+            vec![1.2, 0.8, 1.0],
+            vec![0.0, 2.0],
+        ];
+        for aggregate in Aggregate::ALL {
+            let ci = bootstrap_ci(&scores, aggregate, 500, 0.95, 7).unwrap();
+            assert_eq!(ci.estimate, aggregate.compute(&scores));
+            assert!(
+                ci.lower <= ci.estimate && ci.estimate <= ci.upper,
+                "{:?}",
+                ci
+            );
+            assert!(ci.lower < ci.upper, "{:?}", ci);
+            // the same seed resamples the same runs
+            assert_eq!(bootstrap_ci(&scores, aggregate, 500, 0.95, 7), Ok(ci));
+        }
+        // a single run per game leaves nothing to resample
+        let ci = bootstrap_ci(&[vec![0.5], vec![2.0]], Aggregate::Mean, 100, 0.9, 0).unwrap();
+        assert_eq!((ci.lower, ci.estimate, ci.upper), (1.25, 1.25, 1.25));
+    }
+
+    #[test]
+    fn bootstrap_rejects_intervals_that_do_not_exist() {
+        let scores = [vec![0.5, 1.0]];
+        for confidence in [0.0, 1.0, -0.5, 95.0, f64::NAN] {
+            assert!(bootstrap_ci(&scores, Aggregate::Mean, 100, confidence, 0).is_err());
+        }
+        assert!(bootstrap_ci(&scores, Aggregate::Mean, 0, 0.95, 0).is_err());
+        assert!(bootstrap_ci(&[], Aggregate::Mean, 100, 0.95, 0).is_err());
+        let scores = [vec![0.5, 1.0], vec![]];
+        assert!(bootstrap_ci(&scores, Aggregate::Mean, 100, 0.95, 0).is_err());
+    }
+
+    #[test]
+    fn percentiles_interpolate_between_ranks() {
+        let sorted = [1.0, 2.0, 4.0, 8.0];
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&sorted, 0.5), 3.0);
+        assert_eq!(percentile(&sorted, 1.0), 8.0);
+    }
+}
@@ -0,0 +1,476 @@
+//! Shared-memory ring of observations, written by `VecAtari` and read by other threads or
+//! processes without serialization.
+//!
+//! The ring keeps the last `capacity` observations of every env. Its memory is laid out as
+//! follows, all integers little endian:
+//!
+//! | offset        | size                               | content                               |
+//! |---------------|------------------------------------|---------------------------------------|
+//! | 0             | 8                                  | magic `b"ALEOBSR1"`                   |
+//! | 8             | 4                                  | `num_envs` (`u32`)                    |
+//! | 12            | 4                                  | `capacity` (`u32`)                    |
+//! | 16            | 4                                  | `height` (`u32`)                      |
+//! | 20            | 4                                  | `width` (`u32`)                       |
+//! | 24            | 4                                  | `channels` (`u32`), 1 or 3            |
+//! | 28            | 36                                 | reserved                              |
+//! | 64            | 8 × `num_envs`                     | `head` of each env (`u64`)            |
+//! | `data_offset` | `num_envs × capacity × obs_size`   | observations                          |
+//!
+//! `data_offset` is the end of the heads rounded up to 64 bytes and `obs_size` is
+//! `height × width × channels`. `head[env]` counts the observations written for `env`; the
+//! observation of timestep `t` is stored at `data_offset + (env × capacity + t % capacity) ×
+//! obs_size` and is valid while `t < head[env]` and `head[env] - t < capacity - 1`. A writer
+//! fills the slot of timestep `head` and then increments `head`, so the slot of timestep
+//! `head - capacity` may be changing before a reader sees the new `head`.
+
+use std::ffi::CString;
+use std::io;
+use std::ptr::null_mut;
+use std::sync::atomic::{self, AtomicU64, Ordering};
+
+const MAGIC: &[u8; 8] = b"ALEOBSR1";
+const HEADER_SIZE: usize = 64;
+
+enum Backing {
+    // named POSIX shared memory, unlinked on drop by the process that created it
+    Shm { name: CString, owner: bool },
+    // memory provided by the caller
+    External,
+}
+
+pub struct ObsRing {
+    ptr: *mut u8,
+    len: usize,
+    backing: Backing,
+    num_envs: usize,
+    capacity: usize,
+    screen_size: (usize, usize),
+    channels: usize,
+}
+
+unsafe impl Send for ObsRing {}
+unsafe impl Sync for ObsRing {}
+
+impl ObsRing {
+    /// Bytes needed by a ring for `num_envs` envs of `capacity` observations each; panics when
+    /// they do not fit in a `usize`.
+    pub fn size(
+        num_envs: usize,
+        capacity: usize,
+        screen_size: (usize, usize),
+        channels: usize,
+    ) -> usize {
+        checked_size(num_envs, capacity, screen_size, channels).expect("Ring size overflows")
+    }
+
+    /// Creates a named shared memory ring, e.g. `"/ale-obs"`, which other processes open with
+    /// `ObsRing::open` (or Python's `SharedMemory("ale-obs")`). The name is unlinked when the
+    /// ring is dropped.
+    pub fn create(
+        name: &str,
+        num_envs: usize,
+        capacity: usize,
+        screen_size: (usize, usize),
+        channels: usize,
+    ) -> io::Result<ObsRing> {
+        check_layout(num_envs, capacity, channels)?;
+        let len = checked_size(num_envs, capacity, screen_size, channels)
+            .ok_or_else(|| invalid("Ring size overflows"))?;
+        let name = shm_name(name)?;
+        let ptr = unsafe {
+            let fd = libc::shm_open(
+                name.as_ptr(),
+                libc::O_CREAT | libc::O_EXCL | libc::O_RDWR,
+                0o600,
+            );
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mapped = match libc::ftruncate(fd, len as libc::off_t) {
+                0 => map(fd, len),
+                _ => Err(io::Error::last_os_error()),
+            };
+            libc::close(fd);
+            if mapped.is_err() {
+                libc::shm_unlink(name.as_ptr());
+            }
+            mapped?
+        };
+        let ring = ObsRing {
+            ptr,
+            len,
+            backing: Backing::Shm { name, owner: true },
+            num_envs,
+            capacity,
+            screen_size,
+            channels,
+        };
+        ring.write_header();
+        Ok(ring)
+    }
+
+    /// Opens a ring created by `ObsRing::create`, possibly in another process.
+    pub fn open(name: &str) -> io::Result<ObsRing> {
+        let name = shm_name(name)?;
+        unsafe {
+            let fd = libc::shm_open(name.as_ptr(), libc::O_RDWR, 0);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut stat: libc::stat = std::mem::zeroed();
+            let mapped = match libc::fstat(fd, &mut stat) {
+                0 => map(fd, stat.st_size as usize),
+                _ => Err(io::Error::last_os_error()),
+            };
+            libc::close(fd);
+            let ptr = mapped?;
+            ObsRing::from_header(
+                ptr,
+                stat.st_size as usize,
+                Backing::Shm { name, owner: false },
+            )
+        }
+    }
+
+    /// Lays a ring out in memory owned by the caller, e.g. a buffer shared with a learner.
+    ///
+    /// # Safety
+    /// `ptr` must be valid for reads and writes of `len` bytes, aligned to 8 bytes, and outlive
+    /// the ring.
+    pub unsafe fn from_raw(
+        ptr: *mut u8,
+        len: usize,
+        num_envs: usize,
+        capacity: usize,
+        screen_size: (usize, usize),
+        channels: usize,
+    ) -> io::Result<ObsRing> {
+        check_layout(num_envs, capacity, channels)?;
+        let size = checked_size(num_envs, capacity, screen_size, channels)
+            .ok_or_else(|| invalid("Ring size overflows"))?;
+        if !ptr.cast::<u64>().is_aligned() || len < size {
+            return Err(invalid("Buffer is misaligned or too small for the ring"));
+        }
+        let ring = ObsRing {
+            ptr,
+            len,
+            backing: Backing::External,
+            num_envs,
+            capacity,
+            screen_size,
+            channels,
+        };
+        ring.write_header();
+        Ok(ring)
+    }
+
+    pub fn num_envs(&self) -> usize {
+        self.num_envs
+    }
+
+    /// Observations kept per env.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// (height, width) of the observations.
+    pub fn screen_dim(&self) -> (usize, usize) {
+        self.screen_size
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Offset of the first observation from the start of the ring.
+    pub fn data_offset(&self) -> usize {
+        data_offset(self.num_envs)
+    }
+
+    /// Bytes of one observation.
+    pub fn obs_size(&self) -> usize {
+        self.screen_size.0 * self.screen_size.1 * self.channels
+    }
+
+    /// Number of observations written for `env`, i.e. the timestep of the next one.
+    pub fn head(&self, env: usize) -> u64 {
+        self.head_of(env).load(Ordering::Acquire)
+    }
+
+    /// Copies the observation of `env` at timestep `t` into `out`; false when it has not been
+    /// written yet or was overwritten before the copy completed.
+    pub fn read(&self, env: usize, t: u64, out: &mut [u8]) -> bool {
+        assert_eq!(
+            out.len(),
+            self.obs_size(),
+            "Output buffer has the wrong size"
+        );
+        let capacity = self.capacity as u64;
+        let head = self.head(env);
+        if t >= head || head - t >= capacity - 1 {
+            return false;
+        }
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.slot(env, t), out.as_mut_ptr(), out.len());
+        }
+        // the writer may have moved on to the slot while copying; the fence keeps the copy
+        // before the second load of the head
+        atomic::fence(Ordering::Acquire);
+        self.head(env) - t < capacity - 1
+    }
+
+    // write the next observation of env in place and publish it
+    pub(crate) fn push(&self, env: usize, write: impl FnOnce(&mut [u8])) {
+        let head = self.head_of(env);
+        let t = head.load(Ordering::Relaxed);
+        let slot = unsafe { std::slice::from_raw_parts_mut(self.slot(env, t), self.obs_size()) };
+        write(slot);
+        head.store(t + 1, Ordering::Release);
+    }
+
+    fn head_of(&self, env: usize) -> &AtomicU64 {
+        assert!(env < self.num_envs, "Env {} is out of range", env);
+        unsafe { &*(self.ptr.add(HEADER_SIZE + 8 * env) as *const AtomicU64) }
+    }
+
+    fn slot(&self, env: usize, t: u64) -> *mut u8 {
+        let index = env * self.capacity + (t % self.capacity as u64) as usize;
+        unsafe {
+            self.ptr
+                .add(data_offset(self.num_envs) + index * self.obs_size())
+        }
+    }
+
+    fn write_header(&self) {
+        let header = unsafe { std::slice::from_raw_parts_mut(self.ptr, HEADER_SIZE) };
+        header.fill(0);
+        header[0..8].copy_from_slice(MAGIC);
+        let fields = [
+            self.num_envs,
+            self.capacity,
+            self.screen_size.0,
+            self.screen_size.1,
+            self.channels,
+        ];
+        for (i, value) in fields.into_iter().enumerate() {
+            header[8 + 4 * i..12 + 4 * i].copy_from_slice(&(value as u32).to_le_bytes());
+        }
+        for env in 0..self.num_envs {
+            self.head_of(env).store(0, Ordering::Release);
+        }
+    }
+
+    // read the layout of an existing ring; the ring is built first so that the mapping is
+    // released on errors
+    unsafe fn from_header(ptr: *mut u8, len: usize, backing: Backing) -> io::Result<ObsRing> {
+        let mut ring = ObsRing {
+            ptr,
+            len,
+            backing,
+            num_envs: 0,
+            capacity: 0,
+            screen_size: (0, 0),
+            channels: 0,
+        };
+        let not_a_ring = || invalid("Shared memory does not hold an observation ring");
+        if len < HEADER_SIZE || std::slice::from_raw_parts(ptr, 8) != MAGIC {
+            return Err(not_a_ring());
+        }
+        let field = |i: usize| {
+            let bytes = std::slice::from_raw_parts(ptr.add(8 + 4 * i), 4);
+            u32::from_le_bytes(bytes.try_into().unwrap()) as usize
+        };
+        ring.num_envs = field(0);
+        ring.capacity = field(1);
+        ring.screen_size = (field(2), field(3));
+        ring.channels = field(4);
+        // the sizes come from the mapped memory, which may have been written by anyone
+        check_layout(ring.num_envs, ring.capacity, ring.channels)?;
+        let size = checked_size(
+            ring.num_envs,
+            ring.capacity,
+            ring.screen_size,
+            ring.channels,
+        );
+        if size.is_none_or(|size| len < size) {
+            return Err(not_a_ring());
+        }
+        Ok(ring)
+    }
+}
+
+impl Drop for ObsRing {
+    fn drop(&mut self) {
+        if let Backing::Shm { name, owner } = &self.backing {
+            unsafe {
+                libc::munmap(self.ptr as *mut libc::c_void, self.len);
+                if *owner {
+                    libc::shm_unlink(name.as_ptr());
+                }
+            }
+        }
+    }
+}
+
+fn data_offset(num_envs: usize) -> usize {
+    (HEADER_SIZE + 8 * num_envs).next_multiple_of(64)
+}
+
+// bytes of a ring, None when they do not fit in a usize
+fn checked_size(
+    num_envs: usize,
+    capacity: usize,
+    screen_size: (usize, usize),
+    channels: usize,
+) -> Option<usize> {
+    let data_offset = num_envs
+        .checked_mul(8)?
+        .checked_add(HEADER_SIZE)?
+        .checked_next_multiple_of(64)?;
+    let data = [capacity, screen_size.0, screen_size.1, channels]
+        .into_iter()
+        .try_fold(num_envs, usize::checked_mul)?;
+    data_offset.checked_add(data)
+}
+
+fn check_layout(num_envs: usize, capacity: usize, channels: usize) -> io::Result<()> {
+    // a reader may see the head one behind the slot being written, so two slots are never
+    // readable
+    if num_envs == 0 || capacity < 3 {
+        return Err(invalid(
+            "An observation ring needs at least one env and three slots",
+        ));
+    }
+    if channels != 1 && channels != 3 {
+        return Err(invalid("Observations have 1 or 3 channels"));
+    }
+    Ok(())
+}
+
+// POSIX shared memory names start with a single slash
+fn shm_name(name: &str) -> io::Result<CString> {
+    let name = match name.starts_with('/') {
+        true => name.to_string(),
+        false => format!("/{}", name),
+    };
+    CString::new(name).map_err(|_| invalid("Shared memory name contains a nul byte"))
+}
+
+unsafe fn map(fd: libc::c_int, len: usize) -> io::Result<*mut u8> {
+    let ptr = libc::mmap(
+        null_mut(),
+        len,
+        libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_SHARED,
+        fd,
+        0,
+    );
+    match ptr == libc::MAP_FAILED {
+        true => Err(io::Error::last_os_error()),
+        false => Ok(ptr as *mut u8),
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    // ring in a u64 buffer, so that it is aligned
+    fn ring(buffer: &mut Vec<u64>, capacity: usize, screen_size: (usize, usize)) -> ObsRing {
+        let size = ObsRing::size(1, capacity, screen_size, 1);
+        buffer.resize(size.div_ceil(8), 0);
+        unsafe {
+            ObsRing::from_raw(
+                buffer.as_mut_ptr().cast(),
+                size,
+                1,
+                capacity,
+                screen_size,
+                1,
+            )
+        }
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_the_latest_observations() {
+        let mut buffer = Vec::new();
+        let ring = ring(&mut buffer, 4, (1, 2));
+        let mut out = [0u8; 2];
+        assert!(!ring.read(0, 0, &mut out));
+        for t in 0..5u8 {
+            ring.push(0, |obs| obs.fill(t));
+        }
+        assert_eq!(ring.head(0), 5);
+        // the slot after the head may be written before a reader sees the new head
+        for t in 0..3 {
+            assert!(!ring.read(0, t, &mut out));
+        }
+        for t in 3..5 {
+            assert!(ring.read(0, t, &mut out));
+            assert_eq!(out, [t as u8; 2]);
+        }
+        assert!(!ring.read(0, 5, &mut out));
+    }
+
+    #[test]
+    fn reads_racing_a_writer_are_never_torn() {
+        let mut buffer = Vec::new();
+        let ring = ring(&mut buffer, 3, (1024, 1024));
+        let last = ring.obs_size() - 1;
+        let stop = AtomicBool::new(false);
+        thread::scope(|scope| {
+            // the writer only marks both ends of a slot, so that it laps the reader mid-copy
+            scope.spawn(|| {
+                let mut t = 0u64;
+                while !stop.load(Ordering::Relaxed) {
+                    ring.push(0, |obs| {
+                        obs[0] = t as u8;
+                        obs[last] = t as u8;
+                    });
+                    t += 1;
+                }
+            });
+            let mut out = vec![0u8; ring.obs_size()];
+            let torn = (0..10_000)
+                .filter(|_| {
+                    let t = ring.head(0).saturating_sub(1);
+                    ring.read(0, t, &mut out) && (out[0], out[last]) != (t as u8, t as u8)
+                })
+                .count();
+            // stop the writer before asserting, or a failure would leave it spinning
+            stop.store(true, Ordering::Relaxed);
+            assert_eq!(torn, 0);
+        });
+    }
+
+    #[test]
+    fn corrupt_headers_are_rejected() {
+        let mut buffer = Vec::new();
+        let ring = ring(&mut buffer, 3, (2, 2));
+        let len = ring.len;
+        drop(ring);
+        let ptr: *mut u8 = buffer.as_mut_ptr().cast();
+        let set_field = |i: usize, value: u32| unsafe {
+            std::ptr::copy_nonoverlapping(value.to_le_bytes().as_ptr(), ptr.add(8 + 4 * i), 4);
+        };
+        assert!(unsafe { ObsRing::from_header(ptr, len, Backing::External) }.is_ok());
+        // sizes whose product overflows
+        set_field(1, u32::MAX);
+        set_field(2, u32::MAX);
+        set_field(3, u32::MAX);
+        assert!(unsafe { ObsRing::from_header(ptr, len, Backing::External) }.is_err());
+        // no slots to read from
+        set_field(1, 0);
+        set_field(2, 2);
+        set_field(3, 2);
+        assert!(unsafe { ObsRing::from_header(ptr, len, Backing::External) }.is_err());
+        assert!(unsafe { ObsRing::from_header(ptr, 8, Backing::External) }.is_err());
+    }
+}
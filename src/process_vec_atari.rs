@@ -0,0 +1,1031 @@
+//! Multi-process backend of `VecAtari`: groups of envs run in child processes, so that a crash
+//! of the emulator only takes down its group, which is then respawned.
+//!
+//! Children are started by executing the worker command (see `set_worker_command`), never by a
+//! bare fork of the caller, whose other threads may hold locks. A child gets its socket as fd 3
+//! and the shared observations as fd 4, a memory file it maps and writes observations straight
+//! into, followed by the last observation of each env whose episode ended in a step. It first
+//! reads a setup message, a `u32` length followed by its envs, their seeds and settings, then
+//! creates its envs and handles commands until the socket closes.
+//!
+//! A step is the byte 0 followed by one action byte per env of the group, a reset is the byte
+//! 1, a seed flag byte and the seed (`i32`, little endian). The child answers with one 12 byte
+//! record per env: reward (`i32`), terminal, truncation, life loss and score flag bytes, then
+//! the score (`i32`). A child that does not answer within `REPLY_TIMEOUT` counts as crashed.
+
+use crate::action::Action;
+use crate::atari::Atari;
+use crate::config::{AleConfig, SettingValue};
+use crate::logger::{self, LoggerMode};
+use crate::rom::BundledRom;
+use crate::seeding::derive_seed;
+use crate::vec_atari::{
+    env_factories, pin_current_thread, shared_spaces, worker_count, worker_envs, write_obs,
+    EnvError, EnvSpec, StepResult, WorkerConfig,
+};
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::panic;
+use std::process::{self, Child, Command, Stdio};
+use std::ptr::null_mut;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const STEP: u8 = 0;
+const RESET: u8 = 1;
+const RECORD_SIZE: usize = 12;
+const SOCKET_FD: RawFd = 3;
+const OBS_FD: RawFd = 4;
+// set in the environment of children, see run_worker_if_spawned
+const WORKER_VAR: &str = "ALE_ENV_WORKER";
+
+/// Time a worker process has to answer a command before it is killed and respawned.
+pub const REPLY_TIMEOUT: Duration = Duration::from_secs(60);
+// time the worker processes have to exit once a ProcessVecAtari is dropped
+const EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+static WORKER_COMMAND: Mutex<Option<(OsString, Vec<OsString>)>> = Mutex::new(None);
+
+/// Sets the program and arguments that start a worker process, which must call
+/// `run_worker_if_spawned` or `worker_main`. Defaults to the current executable without
+/// arguments, so a Rust program using the default must call `run_worker_if_spawned` first in
+/// `main`; the Python package runs `python -m ale_env._worker`.
+pub fn set_worker_command(program: impl Into<OsString>, args: Vec<OsString>) {
+    *WORKER_COMMAND.lock().unwrap() = Some((program.into(), args));
+}
+
+/// Serves the envs of a `ProcessVecAtari` and exits when this process was started as one of
+/// its workers, otherwise returns.
+///
+/// **Programs using the default worker command must call it first in `main`.** Their workers
+/// run the program's own executable, which would otherwise run the program again instead of
+/// serving envs; `ProcessVecAtari::try_with_specs` returns an error in such a process.
+pub fn run_worker_if_spawned() {
+    if env::var_os(WORKER_VAR).is_some() {
+        worker_main()
+    }
+}
+
+/// Body of a worker process: serves its envs until the parent closes the socket, then exits.
+pub fn worker_main() -> ! {
+    let code = match panic::catch_unwind(serve) {
+        Ok(Ok(())) => 0,
+        Ok(Err(e)) => {
+            eprintln!("ale-env worker failed: {}", e);
+            1
+        }
+        Err(_) => 101,
+    };
+    process::exit(code)
+}
+
+// shared mapping of a memory file, holding the observation of every env, then its final
+// observation
+struct SharedObs {
+    file: OwnedFd,
+    ptr: *mut u8,
+    len: usize,
+    num_envs: usize,
+    obs_size: usize,
+}
+
+unsafe impl Send for SharedObs {}
+unsafe impl Sync for SharedObs {}
+
+impl SharedObs {
+    fn new(num_envs: usize, obs_size: usize) -> io::Result<SharedObs> {
+        let file = memory_file()?;
+        let len = 2 * num_envs * obs_size;
+        if unsafe { libc::ftruncate(file.as_raw_fd(), len as libc::off_t) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        SharedObs::map(file, num_envs, obs_size)
+    }
+
+    fn map(file: OwnedFd, num_envs: usize, obs_size: usize) -> io::Result<SharedObs> {
+        let len = 2 * num_envs * obs_size;
+        let ptr = unsafe {
+            libc::mmap(
+                null_mut(),
+                len.max(1),
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(SharedObs {
+            file,
+            ptr: ptr as *mut u8,
+            len,
+            num_envs,
+            obs_size,
+        })
+    }
+
+    // observation of one env; the child of its group writes it while handling a command, and
+    // the parent only reads it once the child has answered
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn env(&self, index: usize) -> &mut [u8] {
+        std::slice::from_raw_parts_mut(self.ptr.add(index * self.obs_size), self.obs_size)
+    }
+
+    // last observation of the episode that ended in the latest step of one env, valid when
+    // its record has the score flag
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn final_obs(&self, index: usize) -> &mut [u8] {
+        self.env(self.num_envs + index)
+    }
+}
+
+impl Drop for SharedObs {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len.max(1));
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn memory_file() -> io::Result<OwnedFd> {
+    match unsafe { libc::memfd_create(c"ale-env-obs".as_ptr(), libc::MFD_CLOEXEC) } {
+        -1 => Err(io::Error::last_os_error()),
+        fd => Ok(unsafe { OwnedFd::from_raw_fd(fd) }),
+    }
+}
+
+// POSIX shared memory object, unlinked right away so that only its descriptors keep it alive
+#[cfg(not(target_os = "linux"))]
+fn memory_file() -> io::Result<OwnedFd> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let name = format!(
+        "/ale-env-{}-{}",
+        process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    );
+    let name = std::ffi::CString::new(name).unwrap();
+    let flags = libc::O_RDWR | libc::O_CREAT | libc::O_EXCL;
+    let fd = unsafe { libc::shm_open(name.as_ptr(), flags, 0o600 as libc::c_uint) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe {
+        libc::shm_unlink(name.as_ptr());
+        libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        Ok(OwnedFd::from_raw_fd(fd))
+    }
+}
+
+struct Worker {
+    child: Child,
+    stream: UnixStream,
+    envs: Range<usize>,
+    // set once the child failed again after a respawn
+    failure: Option<String>,
+}
+
+// what a child needs to create its envs, sent as its setup message
+struct Setup {
+    num_envs: usize,
+    screen_size: (usize, usize),
+    channels: usize,
+    max_frames: u32,
+    gray_scale: bool,
+    max_episode_steps: Option<u32>,
+    config: AleConfig,
+    logger_mode: LoggerMode,
+    // index, spec and seed of each env of the child
+    envs: Vec<(usize, EnvSpec, i32)>,
+}
+
+/// `VecAtari` running its envs in child processes, with the same methods.
+///
+/// A child that crashes or stops answering, e.g. on a segfault in the emulator, is respawned
+/// with the current seeds of its envs. Its envs then report `truncation` with the first
+/// observation of a new episode, the score and last observation of the lost one, and the
+/// crash is kept for `take_recovered_errors`.
+pub struct ProcessVecAtari {
+    workers: Vec<Worker>,
+    // kept to set up respawned children
+    specs: Vec<EnvSpec>,
+    max_frames: u32,
+    gray_scale: bool,
+    max_episode_steps: Option<u32>,
+    config: AleConfig,
+    obs: SharedObs,
+    cpus: Option<Vec<usize>>,
+    games: Vec<BundledRom>,
+    seeds: Vec<i32>,
+    action_space: Vec<Action>,
+    screen_size: (usize, usize),
+    channels: usize,
+    // score of the running episode of each env, reported when a crash ends it
+    scores: Vec<i32>,
+    recovered_errors: Vec<EnvError>,
+}
+
+impl ProcessVecAtari {
+    pub fn new(
+        num_envs: usize,
+        game: &str,
+        max_frames: u32,
+        gray_scale: bool,
+        seed: i32,
+        max_episode_steps: Option<u32>,
+        config: Option<AleConfig>,
+    ) -> Self {
+        let spec = EnvSpec::from(BundledRom::name2rom(game));
+        ProcessVecAtari::with_specs(
+            vec![spec; num_envs],
+            max_frames,
+            gray_scale,
+            seed,
+            max_episode_steps,
+            config,
+            None,
+        )
+    }
+
+    // one env per spec as in VecAtari::with_specs, workers are child processes; failed envs
+    // are always re-created; panics where try_with_specs returns an error
+    pub fn with_specs(
+        specs: Vec<EnvSpec>,
+        max_frames: u32,
+        gray_scale: bool,
+        seed: i32,
+        max_episode_steps: Option<u32>,
+        config: Option<AleConfig>,
+        workers: Option<WorkerConfig>,
+    ) -> Self {
+        ProcessVecAtari::try_with_specs(
+            specs,
+            max_frames,
+            gray_scale,
+            seed,
+            max_episode_steps,
+            config,
+            workers,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // with_specs returning an error as VecAtari::try_with_specs does, when this process is
+    // itself a worker, or when the shared observations cannot be mapped or a worker process
+    // cannot be started
+    pub fn try_with_specs(
+        specs: Vec<EnvSpec>,
+        max_frames: u32,
+        gray_scale: bool,
+        seed: i32,
+        max_episode_steps: Option<u32>,
+        config: Option<AleConfig>,
+        workers: Option<WorkerConfig>,
+    ) -> Result<Self, String> {
+        // a worker running the caller's program again would start workers of its own, without end
+        if env::var_os(WORKER_VAR).is_some() {
+            return Err(format!(
+                "This process is a ProcessVecAtari worker ({} is set); call \
+                 run_worker_if_spawned first in main",
+                WORKER_VAR
+            ));
+        }
+        if specs.is_empty() {
+            return Err("VecAtari needs at least one env".to_string());
+        }
+        let workers = workers.unwrap_or_default();
+        let num_envs = specs.len();
+        let num_workers = worker_count(&workers, num_envs)?;
+        let config = config.unwrap_or_default();
+        let seeds: Vec<i32> = (0..num_envs).map(|i| derive_seed(seed, i)).collect();
+
+        // probe one env per game in this process for the action space and screen sizes
+        let make_envs = env_factories(&specs, max_frames, gray_scale, max_episode_steps, &config);
+        let mut probes: Vec<Atari> = Vec::new();
+        for (spec, make_env) in specs.iter().zip(&make_envs) {
+            if probes.iter().all(|env| env.rom() != spec.game) {
+                probes.push(make_env(seed)?);
+            }
+        }
+        let (action_space, screen_size) = shared_spaces(&probes)?;
+        drop(probes);
+        let channels = match gray_scale {
+            true => 1,
+            false => 3,
+        };
+
+        let obs = SharedObs::new(num_envs, screen_size.0 * screen_size.1 * channels)
+            .map_err(|e| format!("Map shared observations failed: {}", e))?;
+        let mut vec_env = ProcessVecAtari {
+            workers: Vec::with_capacity(num_workers),
+            games: specs.iter().map(|spec| spec.game).collect(),
+            specs,
+            max_frames,
+            gray_scale,
+            max_episode_steps,
+            config,
+            obs,
+            cpus: workers.cpus,
+            seeds,
+            action_space,
+            screen_size,
+            channels,
+            scores: vec![0; num_envs],
+            recovered_errors: Vec::new(),
+        };
+        // on an error, dropping vec_env stops the workers started so far
+        for (w, envs) in worker_envs(num_envs, num_workers).into_iter().enumerate() {
+            let worker = vec_env
+                .spawn(w, envs)
+                .map_err(|e| format!("Start worker process failed: {}", e))?;
+            vec_env.workers.push(worker);
+        }
+        Ok(vec_env)
+    }
+
+    // panics when a respawned worker failed too, see try_step
+    pub fn step(&mut self, actions: Vec<Action>) -> Vec<StepResult> {
+        self.try_step(actions).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // step all envs; an error is only returned when a crashed worker could not be respawned
+    pub fn try_step(&mut self, actions: Vec<Action>) -> Result<Vec<StepResult>, EnvError> {
+        assert_eq!(
+            actions.len(),
+            self.num_envs(),
+            "Expected one action per env"
+        );
+        let commands = self
+            .workers
+            .iter()
+            .map(|worker| {
+                let mut command = vec![STEP];
+                command.extend(actions[worker.envs.clone()].iter().map(|&a| a as u8));
+                command
+            })
+            .collect();
+        self.run(commands)
+    }
+
+    // panics when a respawned worker failed too, see try_reset
+    pub fn reset(&mut self, seed: Option<i32>) -> Vec<StepResult> {
+        self.try_reset(seed).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // reset all envs; a given base seed reseeds env i with derive_seed(seed, i)
+    pub fn try_reset(&mut self, seed: Option<i32>) -> Result<Vec<StepResult>, EnvError> {
+        if let Some(seed) = seed {
+            for (i, env_seed) in self.seeds.iter_mut().enumerate() {
+                *env_seed = derive_seed(seed, i);
+            }
+        }
+        let command = reset_command(seed);
+        self.run(vec![command; self.workers.len()])
+    }
+
+    // return the worker crashes that were recovered from since the last call
+    pub fn take_recovered_errors(&mut self) -> Vec<EnvError> {
+        std::mem::take(&mut self.recovered_errors)
+    }
+
+    pub fn num_envs(&self) -> usize {
+        self.games.len()
+    }
+
+    // return the game run by each env
+    pub fn games(&self) -> &[BundledRom] {
+        &self.games
+    }
+
+    pub fn action_space(&self) -> &[Action] {
+        &self.action_space
+    }
+
+    // return the seed each env is currently running with
+    pub fn seeds(&self) -> Vec<i32> {
+        self.seeds.clone()
+    }
+
+    // return (height, width) of the observations, shared by all envs
+    pub fn screen_dim(&self) -> (usize, usize) {
+        self.screen_size
+    }
+
+    // send every worker its command before waiting, so that the children work in parallel;
+    // every reply is read before returning, so that none is left for the next call
+    fn run(&mut self, commands: Vec<Vec<u8>>) -> Result<Vec<StepResult>, EnvError> {
+        let sent: Vec<bool> = self
+            .workers
+            .iter()
+            .zip(&commands)
+            .map(|(worker, command)| {
+                worker.failure.is_none() && send_all(&worker.stream, command).is_ok()
+            })
+            .collect();
+
+        let mut error = None;
+        let mut results = Vec::with_capacity(self.num_envs());
+        for (w, sent) in sent.into_iter().enumerate() {
+            if let Some(message) = &self.workers[w].failure {
+                let message = format!("failed in an earlier call: {}", message);
+                error.get_or_insert(self.worker_error(w, message));
+                continue;
+            }
+            let answer = match sent {
+                true => read_records(&mut self.workers[w]).ok(),
+                false => None,
+            };
+            let envs = self.workers[w].envs.clone();
+            // the episodes of a crashed child end at the observations it wrote last
+            let (records, last_obs) = match answer {
+                Some(records) => (records, None),
+                None => {
+                    let last_obs: Vec<Vec<u8>> = envs
+                        .clone()
+                        .map(|index| unsafe { self.obs.env(index).to_vec() })
+                        .collect();
+                    match self.respawn(w) {
+                        Ok(records) => (records, Some(last_obs)),
+                        Err(e) => {
+                            error.get_or_insert(e);
+                            continue;
+                        }
+                    }
+                }
+            };
+            for (i, (index, record)) in envs.zip(records.chunks(RECORD_SIZE)).enumerate() {
+                let obs = unsafe { self.obs.env(index).to_vec() };
+                let mut result = decode_record(record, index, self.games[index], obs);
+                match &last_obs {
+                    Some(last_obs) => {
+                        result.truncation = true;
+                        result.score = Some(self.scores[index]);
+                        result.final_obs = Some(last_obs[i].clone());
+                    }
+                    None => {
+                        result.final_obs = result
+                            .score
+                            .map(|_| unsafe { self.obs.final_obs(index).to_vec() });
+                    }
+                }
+                self.scores[index] = match result.score.is_some() || commands[w][0] == RESET {
+                    true => 0,
+                    false => self.scores[index] + result.reward,
+                };
+                results.push(result);
+            }
+        }
+        match error {
+            Some(error) => Err(error),
+            None => Ok(results),
+        }
+    }
+
+    // replace a crashed child and reset its envs; a child that fails again is killed and its
+    // envs fail every later call
+    fn respawn(&mut self, w: usize) -> Result<Vec<u8>, EnvError> {
+        let status = reap(&mut self.workers[w].child);
+        let envs = self.workers[w].envs.clone();
+        let records = self.spawn(w, envs).and_then(|mut worker| {
+            let records = send_all(&worker.stream, &reset_command(None))
+                .and_then(|_| read_records(&mut worker));
+            if records.is_err() {
+                reap(&mut worker.child);
+            }
+            self.workers[w] = worker;
+            records
+        });
+        match records {
+            Ok(records) => {
+                self.recovered_errors.push(self.worker_error(w, status));
+                Ok(records)
+            }
+            Err(e) => {
+                let message = format!("{}, and failed again after a respawn: {}", status, e);
+                self.workers[w].failure = Some(message.clone());
+                Err(self.worker_error(w, message))
+            }
+        }
+    }
+
+    fn worker_error(&self, w: usize, message: String) -> EnvError {
+        let envs = &self.workers[w].envs;
+        EnvError {
+            index: envs.start,
+            game: self.games[envs.start],
+            message: format!(
+                "worker process of envs {}..{} {}",
+                envs.start, envs.end, message
+            ),
+        }
+    }
+
+    // start a child for the envs of worker w and send it its setup
+    fn spawn(&self, w: usize, envs: Range<usize>) -> io::Result<Worker> {
+        let worker_command = WORKER_COMMAND.lock().unwrap().clone();
+        let (program, args) = match worker_command {
+            Some(command) => command,
+            None => (env::current_exe()?.into(), Vec::new()),
+        };
+        let (stream, child_stream) = UnixStream::pair()?;
+        let socket_fd = child_stream.as_raw_fd();
+        let obs_fd = self.obs.file.as_raw_fd();
+        let cpu = self.cpus.as_ref().map(|cpus| cpus[w % cpus.len()]);
+
+        let mut command = Command::new(program);
+        command.args(args).env(WORKER_VAR, "1").stdin(Stdio::null());
+        // runs between fork and exec, where only async-signal-safe calls are allowed
+        unsafe {
+            command.pre_exec(move || {
+                inherit_fds(socket_fd, obs_fd)?;
+                #[cfg(target_os = "linux")]
+                libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+                match cpu {
+                    Some(cpu) => pin_current_thread(cpu),
+                    None => Ok(()),
+                }
+            });
+        }
+        let child = command.spawn()?;
+        drop(child_stream);
+
+        let mut worker = Worker {
+            child,
+            stream,
+            envs: envs.clone(),
+            failure: None,
+        };
+        let setup = self.setup(envs).encode();
+        let sent = worker
+            .stream
+            .set_read_timeout(Some(REPLY_TIMEOUT))
+            .and_then(|_| send_all(&worker.stream, &setup));
+        if let Err(e) = sent {
+            reap(&mut worker.child);
+            return Err(e);
+        }
+        Ok(worker)
+    }
+
+    fn setup(&self, envs: Range<usize>) -> Setup {
+        Setup {
+            num_envs: self.num_envs(),
+            screen_size: self.screen_size,
+            channels: self.channels,
+            max_frames: self.max_frames,
+            gray_scale: self.gray_scale,
+            max_episode_steps: self.max_episode_steps,
+            config: self.config.clone(),
+            logger_mode: logger::logger_mode(),
+            envs: envs.map(|i| (i, self.specs[i], self.seeds[i])).collect(),
+        }
+    }
+}
+
+impl Drop for ProcessVecAtari {
+    fn drop(&mut self) {
+        // closing the sockets makes the children exit; those still running after EXIT_TIMEOUT,
+        // e.g. stuck in the emulator, are killed
+        let deadline = Instant::now() + EXIT_TIMEOUT;
+        let mut children: Vec<Child> = self.workers.drain(..).map(|worker| worker.child).collect();
+        while Instant::now() < deadline {
+            children.retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+            if children.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        for mut child in children {
+            reap(&mut child);
+        }
+    }
+}
+
+// give a child its socket as SOCKET_FD and the observations as OBS_FD, without close-on-exec;
+// both are first copied above OBS_FD so that moving one cannot close the other
+unsafe fn inherit_fds(socket_fd: RawFd, obs_fd: RawFd) -> io::Result<()> {
+    let socket_copy = libc::fcntl(socket_fd, libc::F_DUPFD_CLOEXEC, OBS_FD + 1);
+    let obs_copy = libc::fcntl(obs_fd, libc::F_DUPFD_CLOEXEC, OBS_FD + 1);
+    if socket_copy == -1
+        || obs_copy == -1
+        || libc::dup2(socket_copy, SOCKET_FD) == -1
+        || libc::dup2(obs_copy, OBS_FD) == -1
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// body of a worker process: create the envs, then handle commands until the socket closes
+fn serve() -> io::Result<()> {
+    let mut stream = unsafe { UnixStream::from_raw_fd(SOCKET_FD) };
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len)?;
+    let mut message = vec![0u8; u32::from_le_bytes(len) as usize];
+    stream.read_exact(&mut message)?;
+    let setup = Setup::decode(&message)?;
+
+    let (screen_size, channels) = (setup.screen_size, setup.channels);
+    let obs_size = screen_size.0 * screen_size.1 * channels;
+    let file = unsafe { OwnedFd::from_raw_fd(OBS_FD) };
+    let obs = SharedObs::map(file, setup.num_envs, obs_size)?;
+    logger::set_logger_mode(setup.logger_mode);
+    let specs: Vec<EnvSpec> = setup.envs.iter().map(|&(_, spec, _)| spec).collect();
+    let make_envs = env_factories(
+        &specs,
+        setup.max_frames,
+        setup.gray_scale,
+        setup.max_episode_steps,
+        &setup.config,
+    );
+    let mut envs: Vec<(usize, Atari)> = setup
+        .envs
+        .iter()
+        .zip(&make_envs)
+        .map(|(&(index, _, seed), make_env)| Ok((index, make_env(seed).map_err(invalid)?)))
+        .collect::<io::Result<_>>()?;
+    let write = |env: &mut Atari, index: usize| {
+        write_obs(env, unsafe { obs.env(index) }, screen_size, channels)
+    };
+
+    let mut tag = [0u8];
+    let mut actions = vec![0u8; envs.len()];
+    let mut seed = [0u8; 5];
+    let mut answer = Vec::with_capacity(envs.len() * RECORD_SIZE);
+    while stream.read_exact(&mut tag).is_ok() {
+        answer.clear();
+        match tag[0] {
+            STEP => {
+                stream.read_exact(&mut actions)?;
+                for ((index, env), &action) in envs.iter_mut().zip(&actions) {
+                    let action = Action::try_from(action as i32).map_err(invalid)?;
+                    let (reward, terminal, truncation, life_loss) = env.step(action);
+                    let score = match terminal || truncation {
+                        true => {
+                            let score = env.get_score();
+                            let final_obs = unsafe { obs.final_obs(*index) };
+                            write_obs(env, final_obs, screen_size, channels);
+                            env.reset(None);
+                            Some(score)
+                        }
+                        false => None,
+                    };
+                    write(env, *index);
+                    encode_record(&mut answer, reward, terminal, truncation, life_loss, score);
+                }
+            }
+            _ => {
+                stream.read_exact(&mut seed)?;
+                let base =
+                    (seed[0] == 1).then(|| i32::from_le_bytes(seed[1..].try_into().unwrap()));
+                for (index, env) in envs.iter_mut() {
+                    env.reset(base.map(|base| derive_seed(base, *index)));
+                    write(env, *index);
+                    encode_record(&mut answer, 0, false, false, false, None);
+                }
+            }
+        }
+        send_all(&stream, &answer)?;
+    }
+    Ok(())
+}
+
+impl Setup {
+    // u32 length, then little endian fields
+    fn encode(&self) -> Vec<u8> {
+        let mut m = Message(vec![0u8; 4]);
+        m.u64(self.num_envs as u64);
+        m.u64(self.screen_size.0 as u64);
+        m.u64(self.screen_size.1 as u64);
+        m.u64(self.channels as u64);
+        m.u32(self.max_frames);
+        m.bool(self.gray_scale);
+        m.option(self.max_episode_steps.map(|steps| steps as i32));
+        let config = &self.config;
+        m.u32(config.repeat_action_probability.to_bits());
+        m.i32(config.frame_skip);
+        m.bool(config.color_averaging);
+        m.i32(config.max_num_frames_per_episode);
+        m.option(config.mode);
+        m.option(config.difficulty);
+        m.bool(config.truncate_on_loss_of_life);
+        m.bool(config.full_action_space);
+        m.u64(config.extra().len() as u64);
+        for (key, value) in config.extra() {
+            m.str(key);
+            match value {
+                SettingValue::Int(v) => {
+                    m.u8(0);
+                    m.i32(*v);
+                }
+                SettingValue::Float(v) => {
+                    m.u8(1);
+                    m.u32(v.to_bits());
+                }
+                SettingValue::Bool(v) => {
+                    m.u8(2);
+                    m.bool(*v);
+                }
+                SettingValue::Str(v) => {
+                    m.u8(3);
+                    m.str(v);
+                }
+            }
+        }
+        m.u8(self.logger_mode as u8);
+        m.u64(self.envs.len() as u64);
+        for (index, spec, seed) in &self.envs {
+            m.u64(*index as u64);
+            m.str(spec.game.name());
+            m.option(spec.mode);
+            m.option(spec.difficulty);
+            m.i32(*seed);
+        }
+        let len = (m.0.len() - 4) as u32;
+        m.0[..4].copy_from_slice(&len.to_le_bytes());
+        m.0
+    }
+
+    // inverse of encode, given the message without its length
+    fn decode(message: &[u8]) -> io::Result<Setup> {
+        let mut r = Reader(message);
+        let num_envs = r.u64()? as usize;
+        let screen_size = (r.u64()? as usize, r.u64()? as usize);
+        let channels = r.u64()? as usize;
+        let max_frames = r.u32()?;
+        let gray_scale = r.bool()?;
+        let max_episode_steps = r.option()?.map(|steps| steps as u32);
+        let mut config = AleConfig::default();
+        config.repeat_action_probability = f32::from_bits(r.u32()?);
+        config.frame_skip = r.i32()?;
+        config.color_averaging = r.bool()?;
+        config.max_num_frames_per_episode = r.i32()?;
+        config.mode = r.option()?;
+        config.difficulty = r.option()?;
+        config.truncate_on_loss_of_life = r.bool()?;
+        config.full_action_space = r.bool()?;
+        for _ in 0..r.u64()? {
+            let key = r.str()?;
+            let value = match r.u8()? {
+                0 => SettingValue::Int(r.i32()?),
+                1 => SettingValue::Float(f32::from_bits(r.u32()?)),
+                2 => SettingValue::Bool(r.bool()?),
+                3 => SettingValue::Str(r.str()?),
+                tag => return Err(invalid(format!("Invalid setting type {}", tag))),
+            };
+            config.set(&key, value);
+        }
+        let logger_mode = match r.u8()? {
+            0 => LoggerMode::Info,
+            1 => LoggerMode::Warning,
+            _ => LoggerMode::Error,
+        };
+        let mut envs = Vec::new();
+        for _ in 0..r.u64()? {
+            let index = r.u64()? as usize;
+            let game = r.str()?.parse().map_err(invalid)?;
+            let spec = EnvSpec {
+                game,
+                mode: r.option()?,
+                difficulty: r.option()?,
+            };
+            envs.push((index, spec, r.i32()?));
+        }
+        Ok(Setup {
+            num_envs,
+            screen_size,
+            channels,
+            max_frames,
+            gray_scale,
+            max_episode_steps,
+            config,
+            logger_mode,
+            envs,
+        })
+    }
+}
+
+// setup message being written
+struct Message(Vec<u8>);
+
+impl Message {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend(v.to_le_bytes());
+    }
+
+    fn i32(&mut self, v: i32) {
+        self.0.extend(v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.0.extend(v.to_le_bytes());
+    }
+
+    fn bool(&mut self, v: bool) {
+        self.u8(v as u8);
+    }
+
+    fn option(&mut self, v: Option<i32>) {
+        self.bool(v.is_some());
+        self.i32(v.unwrap_or(0));
+    }
+
+    fn str(&mut self, v: &str) {
+        self.u64(v.len() as u64);
+        self.0.extend(v.as_bytes());
+    }
+}
+
+// setup message being read
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> io::Result<&[u8]> {
+        if self.0.len() < len {
+            return Err(invalid("Truncated setup message"));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn bool(&mut self) -> io::Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn option(&mut self) -> io::Result<Option<i32>> {
+        let some = self.bool()?;
+        let v = self.i32()?;
+        Ok(some.then_some(v))
+    }
+
+    fn str(&mut self) -> io::Result<String> {
+        let len = self.u64()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).map_err(invalid)
+    }
+}
+
+fn invalid(e: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn reset_command(seed: Option<i32>) -> Vec<u8> {
+    let mut command = vec![RESET, seed.is_some() as u8];
+    command.extend(seed.unwrap_or(0).to_le_bytes());
+    command
+}
+
+fn encode_record(
+    answer: &mut Vec<u8>,
+    reward: i32,
+    terminal: bool,
+    truncation: bool,
+    life_loss: bool,
+    score: Option<i32>,
+) {
+    answer.extend(reward.to_le_bytes());
+    answer.extend([
+        terminal as u8,
+        truncation as u8,
+        life_loss as u8,
+        score.is_some() as u8,
+    ]);
+    answer.extend(score.unwrap_or(0).to_le_bytes());
+}
+
+fn decode_record(record: &[u8], index: usize, game: BundledRom, obs: Vec<u8>) -> StepResult {
+    StepResult {
+        index,
+        game,
+        obs,
+        reward: i32::from_le_bytes(record[0..4].try_into().unwrap()),
+        terminal: record[4] == 1,
+        truncation: record[5] == 1,
+        life_loss: record[6] == 1,
+        score: (record[7] == 1).then(|| i32::from_le_bytes(record[8..12].try_into().unwrap())),
+        final_obs: None,
+    }
+}
+
+// fails after REPLY_TIMEOUT, the read timeout of the socket
+fn read_records(worker: &mut Worker) -> io::Result<Vec<u8>> {
+    let mut records = vec![0u8; worker.envs.len() * RECORD_SIZE];
+    worker.stream.read_exact(&mut records)?;
+    Ok(records)
+}
+
+// write to a socket whose peer may have died without raising SIGPIPE
+fn send_all(stream: &UnixStream, mut buf: &[u8]) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    const FLAGS: libc::c_int = libc::MSG_NOSIGNAL;
+    #[cfg(not(target_os = "linux"))]
+    const FLAGS: libc::c_int = 0;
+    while !buf.is_empty() {
+        let sent = unsafe {
+            libc::send(
+                stream.as_raw_fd(),
+                buf.as_ptr() as *const libc::c_void,
+                buf.len(),
+                FLAGS,
+            )
+        };
+        if sent < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(e);
+        }
+        buf = &buf[sent as usize..];
+    }
+    Ok(())
+}
+
+// kill a crashed or unresponsive child, wait for it and describe how it ended
+fn reap(child: &mut Child) -> String {
+    let _ = child.kill();
+    match child.wait() {
+        Ok(status) => match (status.code(), status.signal()) {
+            (Some(code), _) => format!("exited with status {}", code),
+            (_, Some(signal)) if signal != libc::SIGKILL => {
+                format!("was killed by signal {}", signal)
+            }
+            _ => "stopped responding".to_string(),
+        },
+        Err(e) => format!("could not be waited for: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setup_message_round_trip() {
+        let mut config = AleConfig::default();
+        config.repeat_action_probability = 0.25;
+        config.frame_skip = 4;
+        config.mode = Some(2);
+        config.set_float("some_float", 0.1);
+        config.set_string("record_screen_dir", "/tmp/frames");
+        let spec = EnvSpec {
+            game: BundledRom::Pong,
+            mode: None,
+            difficulty: Some(1),
+        };
+        let setup = Setup {
+            num_envs: 8,
+            screen_size: (210, 160),
+            channels: 3,
+            max_frames: 108_000,
+            gray_scale: false,
+            max_episode_steps: Some(27_000),
+            config,
+            logger_mode: LoggerMode::Warning,
+            envs: vec![(4, spec, -7), (5, spec, 123)],
+        };
+
+        let message = setup.encode();
+        let len = u32::from_le_bytes(message[..4].try_into().unwrap()) as usize;
+        assert_eq!(len, message.len() - 4);
+        let decoded = Setup::decode(&message[4..]).unwrap();
+        assert_eq!(decoded.envs, setup.envs);
+        assert_eq!(decoded.screen_size, (210, 160));
+        assert_eq!(decoded.max_episode_steps, Some(27_000));
+        assert_eq!(decoded.logger_mode, LoggerMode::Warning);
+        assert_eq!(decoded.config.repeat_action_probability, 0.25);
+        assert_eq!(decoded.config.frame_skip, 4);
+        assert_eq!(decoded.config.mode, Some(2));
+        assert_eq!(decoded.config.extra(), setup.config.extra());
+        assert!(Setup::decode(&message[4..message.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,1273 @@
+//! Python bindings, built with the `python` feature.
+
+use crate::action::Action;
+use crate::action_log::{ActionLog, ReplayMismatch};
+use crate::atari::Atari;
+use crate::config::AleConfig;
+use crate::dataset::{self, DatasetOptions};
+use crate::logger::{self, LoggerMode};
+use crate::metrics::{self, Aggregate, Normalization};
+#[cfg(unix)]
+use crate::obs_ring::ObsRing;
+#[cfg(unix)]
+use crate::process_vec_atari::{self, ProcessVecAtari};
+use crate::replay::{ReplayBuffer, ReplayConfig};
+use crate::rom::BundledRom;
+use crate::seeding;
+use crate::suites::Suite;
+use crate::trajectory::{RecordingOptions, Trajectory};
+use crate::vec_atari::{EnvError, EnvSpec, StepResult, VecAtari, WorkerConfig};
+use crate::video::{self, VideoOptions};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::sync::Arc;
+
+type PyStepResult = (
+    usize,
+    Vec<u8>,
+    i32,
+    bool,
+    bool,
+    bool,
+    Option<i32>,
+    &'static str,
+    Option<Vec<u8>>,
+);
+
+// a `PyStepResult` as passed back from Python
+type PyStepResultArg = (
+    usize,
+    Vec<u8>,
+    i32,
+    bool,
+    bool,
+    bool,
+    Option<i32>,
+    String,
+    Option<Vec<u8>>,
+);
+
+type PyBatch<'py> = (
+    Vec<usize>,
+    Bound<'py, PyBytes>,
+    Vec<i32>,
+    Vec<f32>,
+    Bound<'py, PyBytes>,
+    Vec<f32>,
+    Vec<f32>,
+);
+
+fn to_action(action: i32) -> PyResult<Action> {
+    Action::try_from(action).map_err(PyValueError::new_err)
+}
+
+fn parse_game(game: &str) -> PyResult<BundledRom> {
+    game.parse::<BundledRom>()
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn worker_config(
+    num_workers: Option<usize>,
+    cpus: Option<Vec<usize>>,
+    recreate_failed_envs: bool,
+) -> PyResult<WorkerConfig> {
+    if num_workers == Some(0) {
+        return Err(PyValueError::new_err("num_workers must be at least 1"));
+    }
+    if cpus.as_ref().is_some_and(|cpus| cpus.is_empty()) {
+        return Err(PyValueError::new_err("cpus must not be empty"));
+    }
+    Ok(WorkerConfig {
+        num_workers,
+        cpus,
+        recreate_failed_envs,
+    })
+}
+
+fn recording_options(chunk_steps: usize, compression: u32) -> PyResult<RecordingOptions> {
+    if chunk_steps == 0 || compression > 9 {
+        return Err(PyValueError::new_err(
+            "chunk_steps must be at least 1 and compression at most 9",
+        ));
+    }
+    Ok(RecordingOptions {
+        chunk_steps,
+        compression,
+    })
+}
+
+fn env_error(error: EnvError) -> PyErr {
+    PyRuntimeError::new_err(error.to_string())
+}
+
+fn step_result_to_tuple(result: StepResult) -> PyStepResult {
+    (
+        result.index,
+        result.obs,
+        result.reward,
+        result.terminal,
+        result.truncation,
+        result.life_loss,
+        result.score,
+        result.game.name(),
+        result.final_obs,
+    )
+}
+
+fn tuple_to_step_result(result: PyStepResultArg) -> PyResult<StepResult> {
+    let (index, obs, reward, terminal, truncation, life_loss, score, game, final_obs) = result;
+    Ok(StepResult {
+        index,
+        game: parse_game(&game)?,
+        obs,
+        reward,
+        terminal,
+        truncation,
+        life_loss,
+        score,
+        final_obs,
+    })
+}
+
+#[pyclass(name = "Atari")]
+pub struct PyAtari {
+    // None once closed
+    inner: Option<Atari>,
+}
+
+#[pymethods]
+impl PyAtari {
+    #[new]
+    #[pyo3(signature = (game, max_frames, gray_scale, seed=None, max_episode_steps=None, config=None))]
+    fn new(
+        game: &str,
+        max_frames: u32,
+        gray_scale: bool,
+        seed: Option<i32>,
+        max_episode_steps: Option<u32>,
+        config: Option<AleConfig>,
+    ) -> PyResult<Self> {
+        let inner = Atari::try_new(
+            game,
+            max_frames,
+            gray_scale,
+            seed,
+            max_episode_steps,
+            config,
+        )
+        .map_err(PyValueError::new_err)?;
+        Ok(PyAtari { inner: Some(inner) })
+    }
+
+    #[pyo3(signature = (seed=None))]
+    fn reset(&mut self, seed: Option<i32>) -> PyResult<()> {
+        self.env_mut()?.reset(seed);
+        Ok(())
+    }
+
+    fn step(&mut self, action: i32) -> PyResult<(i32, bool, bool, bool)> {
+        Ok(self.env_mut()?.step(to_action(action)?))
+    }
+
+    fn obs(&mut self) -> PyResult<Vec<u8>> {
+        Ok(self.env_mut()?.obs())
+    }
+
+    fn screen_dim(&self) -> PyResult<(usize, usize)> {
+        Ok(self.env()?.screen_dim())
+    }
+
+    fn get_action_set(&self) -> PyResult<Vec<i32>> {
+        Ok(self
+            .env()?
+            .get_action_set()
+            .into_iter()
+            .map(i32::from)
+            .collect())
+    }
+
+    fn get_score(&self) -> PyResult<i32> {
+        Ok(self.env()?.get_score())
+    }
+
+    fn episode_frame_number(&self) -> PyResult<i32> {
+        Ok(self.env()?.episode_frame_number())
+    }
+
+    fn episode_steps(&self) -> PyResult<i32> {
+        Ok(self.env()?.episode_steps())
+    }
+
+    fn get_config(&self) -> PyResult<AleConfig> {
+        Ok(self.env()?.get_config())
+    }
+
+    fn available_modes(&self) -> PyResult<Vec<i32>> {
+        Ok(self.env()?.available_modes())
+    }
+
+    fn available_difficulties(&self) -> PyResult<Vec<i32>> {
+        Ok(self.env()?.available_difficulties())
+    }
+
+    fn get_int(&self, key: &str) -> PyResult<i32> {
+        self.env()?.get_int(key).map_err(PyValueError::new_err)
+    }
+
+    fn get_float(&self, key: &str) -> PyResult<f32> {
+        self.env()?.get_float(key).map_err(PyValueError::new_err)
+    }
+
+    fn get_bool(&self, key: &str) -> PyResult<bool> {
+        self.env()?.get_bool(key).map_err(PyValueError::new_err)
+    }
+
+    fn get_string(&self, key: &str) -> PyResult<String> {
+        self.env()?.get_string(key).map_err(PyValueError::new_err)
+    }
+
+    fn set_int(&mut self, key: &str, value: i32) -> PyResult<()> {
+        self.env_mut()?
+            .set_int(key, value)
+            .map_err(PyValueError::new_err)
+    }
+
+    fn set_float(&mut self, key: &str, value: f32) -> PyResult<()> {
+        self.env_mut()?
+            .set_float(key, value)
+            .map_err(PyValueError::new_err)
+    }
+
+    fn set_bool(&mut self, key: &str, value: bool) -> PyResult<()> {
+        self.env_mut()?
+            .set_bool(key, value)
+            .map_err(PyValueError::new_err)
+    }
+
+    fn set_string(&mut self, key: &str, value: &str) -> PyResult<()> {
+        self.env_mut()?
+            .set_string(key, value)
+            .map_err(PyValueError::new_err)
+    }
+
+    fn get_seed(&self) -> PyResult<i32> {
+        Ok(self.env()?.get_seed())
+    }
+
+    fn clone_state(&self) -> PyResult<Vec<u8>> {
+        Ok(self.env()?.clone_state())
+    }
+
+    fn restore_state(&mut self, state: Vec<u8>) -> PyResult<()> {
+        self.env_mut()?
+            .restore_state(&state)
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Writes every episode from the next reset on to its own file in `dir`.
+    #[pyo3(signature = (dir, chunk_steps=1000, compression=6))]
+    fn start_recording(&mut self, dir: &str, chunk_steps: usize, compression: u32) -> PyResult<()> {
+        let options = recording_options(chunk_steps, compression)?;
+        Ok(self.env_mut()?.start_recording(dir, options)?)
+    }
+
+    fn stop_recording(&mut self) -> PyResult<()> {
+        Ok(self.env_mut()?.stop_recording()?)
+    }
+
+    fn is_recording(&self) -> PyResult<bool> {
+        Ok(self.env()?.is_recording())
+    }
+
+    /// Resets with `seed` and logs the actions of the episode from here on, until the next
+    /// reset.
+    #[pyo3(signature = (seed, screen_checksums=false))]
+    fn start_action_log(&mut self, seed: i32, screen_checksums: bool) -> PyResult<()> {
+        self.env_mut()?
+            .start_action_log(seed, screen_checksums)
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Stops logging actions and saves the log to `path`, also after the episode was reset.
+    fn save_action_log(&mut self, path: &str) -> PyResult<()> {
+        let log = self
+            .env_mut()?
+            .take_action_log()
+            .ok_or_else(|| PyRuntimeError::new_err("No action log was started"))?;
+        Ok(log.save(path)?)
+    }
+
+    /// RGB screen, whatever the observation type.
+    fn rgb_screen(&self) -> PyResult<Vec<u8>> {
+        Ok(self.env()?.rgb_screen())
+    }
+
+    /// Saves the screen as a PNG file.
+    fn save_screen(&self, path: &str) -> PyResult<()> {
+        Ok(self.env()?.save_screen(path)?)
+    }
+
+    /// PNG encoded RGB screen, e.g. for logging frames to a dashboard.
+    fn screen_png<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        Ok(PyBytes::new(py, &self.env()?.screen_png()?))
+    }
+
+    /// Writes the screen to a .gif, .png (APNG) or .y4m video after every reset and step.
+    #[pyo3(signature = (path, fps=60, scale=2, overlay=true))]
+    fn start_video(&mut self, path: &str, fps: u32, scale: usize, overlay: bool) -> PyResult<()> {
+        let options = VideoOptions {
+            fps,
+            scale,
+            overlay,
+        };
+        Ok(self.env_mut()?.start_video(path, options)?)
+    }
+
+    fn finish_video(&mut self) -> PyResult<()> {
+        Ok(self.env_mut()?.finish_video()?)
+    }
+
+    fn rom_md5(&self) -> PyResult<&str> {
+        Ok(self.env()?.rom_md5())
+    }
+
+    fn screen_checksum(&self) -> PyResult<u64> {
+        Ok(self.env()?.screen_checksum())
+    }
+
+    /// Releases the emulator, finishing any recording or video; later calls raise an error.
+    fn close(&mut self) {
+        self.inner = None;
+    }
+}
+
+impl PyAtari {
+    fn env(&self) -> PyResult<&Atari> {
+        self.inner.as_ref().ok_or_else(closed)
+    }
+
+    fn env_mut(&mut self) -> PyResult<&mut Atari> {
+        self.inner.as_mut().ok_or_else(closed)
+    }
+}
+
+fn closed() -> PyErr {
+    PyRuntimeError::new_err("Atari env is closed")
+}
+
+// threaded or multi-process implementation behind the Python VecAtari
+enum Backend {
+    Threads(VecAtari),
+    #[cfg(unix)]
+    Processes(ProcessVecAtari),
+}
+
+impl Backend {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        backend: &str,
+        specs: Vec<EnvSpec>,
+        max_frames: u32,
+        gray_scale: bool,
+        seed: i32,
+        max_episode_steps: Option<u32>,
+        config: Option<AleConfig>,
+        workers: WorkerConfig,
+    ) -> PyResult<Backend> {
+        match backend {
+            "threads" => Ok(Backend::Threads(
+                VecAtari::try_with_specs(
+                    specs,
+                    max_frames,
+                    gray_scale,
+                    seed,
+                    max_episode_steps,
+                    config,
+                    Some(workers),
+                )
+                .map_err(PyValueError::new_err)?,
+            )),
+            #[cfg(unix)]
+            "processes" => Ok(Backend::Processes(
+                ProcessVecAtari::try_with_specs(
+                    specs,
+                    max_frames,
+                    gray_scale,
+                    seed,
+                    max_episode_steps,
+                    config,
+                    Some(workers),
+                )
+                .map_err(PyValueError::new_err)?,
+            )),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown backend {}, expected \"threads\" or \"processes\"",
+                backend
+            ))),
+        }
+    }
+
+    fn try_step(&mut self, actions: Vec<Action>) -> Result<Vec<StepResult>, EnvError> {
+        match self {
+            Backend::Threads(envs) => envs.try_step(actions),
+            #[cfg(unix)]
+            Backend::Processes(envs) => envs.try_step(actions),
+        }
+    }
+
+    fn try_reset(&mut self, seed: Option<i32>) -> Result<Vec<StepResult>, EnvError> {
+        match self {
+            Backend::Threads(envs) => envs.try_reset(seed),
+            #[cfg(unix)]
+            Backend::Processes(envs) => envs.try_reset(seed),
+        }
+    }
+
+    fn take_recovered_errors(&mut self) -> Vec<EnvError> {
+        match self {
+            Backend::Threads(envs) => envs.take_recovered_errors(),
+            #[cfg(unix)]
+            Backend::Processes(envs) => envs.take_recovered_errors(),
+        }
+    }
+
+    fn num_envs(&self) -> usize {
+        match self {
+            Backend::Threads(envs) => envs.num_envs(),
+            #[cfg(unix)]
+            Backend::Processes(envs) => envs.num_envs(),
+        }
+    }
+
+    fn games(&self) -> &[BundledRom] {
+        match self {
+            Backend::Threads(envs) => envs.games(),
+            #[cfg(unix)]
+            Backend::Processes(envs) => envs.games(),
+        }
+    }
+
+    fn action_space(&self) -> &[Action] {
+        match self {
+            Backend::Threads(envs) => envs.action_space(),
+            #[cfg(unix)]
+            Backend::Processes(envs) => envs.action_space(),
+        }
+    }
+
+    fn seeds(&self) -> Vec<i32> {
+        match self {
+            Backend::Threads(envs) => envs.seeds(),
+            #[cfg(unix)]
+            Backend::Processes(envs) => envs.seeds(),
+        }
+    }
+
+    fn screen_dim(&self) -> (usize, usize) {
+        match self {
+            Backend::Threads(envs) => envs.screen_dim(),
+            #[cfg(unix)]
+            Backend::Processes(envs) => envs.screen_dim(),
+        }
+    }
+}
+
+#[pyclass(name = "VecAtari")]
+pub struct PyVecAtari {
+    inner: Backend,
+}
+
+#[pymethods]
+impl PyVecAtari {
+    #[new]
+    #[pyo3(signature = (num_envs, game, max_frames, gray_scale, seed, max_episode_steps=None, config=None, num_workers=None, cpus=None, recreate_failed_envs=false, backend="threads"))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        num_envs: usize,
+        game: &str,
+        max_frames: u32,
+        gray_scale: bool,
+        seed: i32,
+        max_episode_steps: Option<u32>,
+        config: Option<AleConfig>,
+        num_workers: Option<usize>,
+        cpus: Option<Vec<usize>>,
+        recreate_failed_envs: bool,
+        backend: &str,
+    ) -> PyResult<Self> {
+        if num_envs == 0 {
+            return Err(PyValueError::new_err("num_envs must be at least 1"));
+        }
+        let spec = EnvSpec::from(parse_game(game)?);
+        Ok(PyVecAtari {
+            inner: Backend::new(
+                backend,
+                vec![spec; num_envs],
+                max_frames,
+                gray_scale,
+                seed,
+                max_episode_steps,
+                config,
+                worker_config(num_workers, cpus, recreate_failed_envs)?,
+            )?,
+        })
+    }
+
+    /// One env per game, with optional per-env modes and difficulties.
+    #[staticmethod]
+    #[pyo3(signature = (games, max_frames, gray_scale, seed, max_episode_steps=None, config=None, modes=None, difficulties=None, num_workers=None, cpus=None, recreate_failed_envs=false, backend="threads"))]
+    #[allow(clippy::too_many_arguments)]
+    fn from_games(
+        games: Vec<String>,
+        max_frames: u32,
+        gray_scale: bool,
+        seed: i32,
+        max_episode_steps: Option<u32>,
+        config: Option<AleConfig>,
+        modes: Option<Vec<Option<i32>>>,
+        difficulties: Option<Vec<Option<i32>>>,
+        num_workers: Option<usize>,
+        cpus: Option<Vec<usize>>,
+        recreate_failed_envs: bool,
+        backend: &str,
+    ) -> PyResult<Self> {
+        if games.is_empty() {
+            return Err(PyValueError::new_err("games must not be empty"));
+        }
+        let modes = modes.unwrap_or_else(|| vec![None; games.len()]);
+        let difficulties = difficulties.unwrap_or_else(|| vec![None; games.len()]);
+        if modes.len() != games.len() || difficulties.len() != games.len() {
+            return Err(PyValueError::new_err(
+                "modes and difficulties must have one entry per game",
+            ));
+        }
+        let specs = games
+            .iter()
+            .zip(modes.into_iter().zip(difficulties))
+            .map(|(game, (mode, difficulty))| {
+                Ok(EnvSpec {
+                    game: parse_game(game)?,
+                    mode,
+                    difficulty,
+                })
+            })
+            .collect::<PyResult<Vec<EnvSpec>>>()?;
+        Ok(PyVecAtari {
+            inner: Backend::new(
+                backend,
+                specs,
+                max_frames,
+                gray_scale,
+                seed,
+                max_episode_steps,
+                config,
+                worker_config(num_workers, cpus, recreate_failed_envs)?,
+            )?,
+        })
+    }
+
+    fn step(&mut self, actions: Vec<i32>) -> PyResult<Vec<PyStepResult>> {
+        if actions.len() != self.inner.num_envs() {
+            return Err(PyValueError::new_err(format!(
+                "Expected {} actions, got {}",
+                self.inner.num_envs(),
+                actions.len()
+            )));
+        }
+        let actions = actions
+            .into_iter()
+            .map(to_action)
+            .collect::<PyResult<Vec<Action>>>()?;
+        let results = self.inner.try_step(actions).map_err(env_error)?;
+        Ok(results.into_iter().map(step_result_to_tuple).collect())
+    }
+
+    #[pyo3(signature = (seed=None))]
+    fn reset(&mut self, seed: Option<i32>) -> PyResult<Vec<PyStepResult>> {
+        let results = self.inner.try_reset(seed).map_err(env_error)?;
+        Ok(results.into_iter().map(step_result_to_tuple).collect())
+    }
+
+    /// Writes observations straight into the ring from now on, leaving them empty in the
+    /// step results; threads backend only.
+    #[cfg(unix)]
+    fn attach_obs_ring(&mut self, ring: &PyObsRing) -> PyResult<()> {
+        let envs = match &mut self.inner {
+            Backend::Threads(envs) => envs,
+            #[cfg(unix)]
+            Backend::Processes(_) => {
+                return Err(PyValueError::new_err(
+                    "Observation rings are only supported by the threads backend",
+                ))
+            }
+        };
+        envs.attach_obs_ring(ring.inner.clone())
+            .map_err(PyValueError::new_err)
+    }
+
+    #[cfg(unix)]
+    fn detach_obs_ring(&mut self) {
+        if let Backend::Threads(envs) = &mut self.inner {
+            envs.detach_obs_ring();
+        }
+    }
+
+    /// Records the episodes of every env from its next reset on, one file each in `dir`.
+    #[pyo3(signature = (dir, chunk_steps=1000, compression=6))]
+    fn start_recording(&mut self, dir: &str, chunk_steps: usize, compression: u32) -> PyResult<()> {
+        let options = recording_options(chunk_steps, compression)?;
+        match &mut self.inner {
+            Backend::Threads(envs) => Ok(envs.start_recording(dir, options)?),
+            #[cfg(unix)]
+            Backend::Processes(_) => Err(PyValueError::new_err(
+                "Recording is only supported by the threads backend",
+            )),
+        }
+    }
+
+    fn stop_recording(&mut self) -> PyResult<()> {
+        match &mut self.inner {
+            Backend::Threads(envs) => Ok(envs.stop_recording()?),
+            #[cfg(unix)]
+            Backend::Processes(_) => Ok(()),
+        }
+    }
+
+    /// Panics of envs that were re-created since the last call.
+    fn take_recovered_errors(&mut self) -> Vec<String> {
+        self.inner
+            .take_recovered_errors()
+            .iter()
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    fn action_space(&self) -> Vec<i32> {
+        self.inner
+            .action_space()
+            .iter()
+            .map(|&action| i32::from(action))
+            .collect()
+    }
+
+    fn games(&self) -> Vec<&'static str> {
+        self.inner.games().iter().map(|game| game.name()).collect()
+    }
+
+    fn seeds(&self) -> Vec<i32> {
+        self.inner.seeds()
+    }
+
+    fn screen_dim(&self) -> (usize, usize) {
+        self.inner.screen_dim()
+    }
+}
+
+#[cfg(unix)]
+#[pyclass(name = "ObsRing")]
+pub struct PyObsRing {
+    inner: Arc<ObsRing>,
+}
+
+#[cfg(unix)]
+#[pymethods]
+impl PyObsRing {
+    #[staticmethod]
+    fn create(
+        name: &str,
+        num_envs: usize,
+        capacity: usize,
+        screen_dim: (usize, usize),
+        channels: usize,
+    ) -> PyResult<Self> {
+        let ring = ObsRing::create(name, num_envs, capacity, screen_dim, channels)?;
+        Ok(PyObsRing {
+            inner: Arc::new(ring),
+        })
+    }
+
+    #[staticmethod]
+    fn open(name: &str) -> PyResult<Self> {
+        Ok(PyObsRing {
+            inner: Arc::new(ObsRing::open(name)?),
+        })
+    }
+
+    fn num_envs(&self) -> usize {
+        self.inner.num_envs()
+    }
+
+    fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    fn screen_dim(&self) -> (usize, usize) {
+        self.inner.screen_dim()
+    }
+
+    fn channels(&self) -> usize {
+        self.inner.channels()
+    }
+
+    fn data_offset(&self) -> usize {
+        self.inner.data_offset()
+    }
+
+    fn head(&self, env: usize) -> PyResult<u64> {
+        self.check_env(env)?;
+        Ok(self.inner.head(env))
+    }
+
+    /// Observation of an env at a timestep, None when not written yet or overwritten.
+    fn read(&self, env: usize, t: u64) -> PyResult<Option<Vec<u8>>> {
+        self.check_env(env)?;
+        let mut obs = vec![0u8; self.inner.obs_size()];
+        Ok(self.inner.read(env, t, &mut obs).then_some(obs))
+    }
+}
+
+#[cfg(unix)]
+impl PyObsRing {
+    fn check_env(&self, env: usize) -> PyResult<()> {
+        match env < self.inner.num_envs() {
+            true => Ok(()),
+            false => Err(PyValueError::new_err(format!(
+                "Env {} is out of range",
+                env
+            ))),
+        }
+    }
+}
+
+#[pyclass(name = "ReplayBuffer")]
+pub struct PyReplayBuffer {
+    inner: ReplayBuffer,
+    num_envs: usize,
+    frame_size: usize,
+}
+
+#[pymethods]
+impl PyReplayBuffer {
+    #[new]
+    #[pyo3(signature = (
+        num_envs,
+        frame_size,
+        capacity=100_000,
+        stack=4,
+        n_step=1,
+        gamma=0.99,
+        priority_exponent=None,
+        seed=0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        num_envs: usize,
+        frame_size: usize,
+        capacity: usize,
+        stack: usize,
+        n_step: usize,
+        gamma: f32,
+        priority_exponent: Option<f64>,
+        seed: u64,
+    ) -> PyResult<Self> {
+        if num_envs == 0 || stack == 0 || n_step == 0 {
+            return Err(PyValueError::new_err(
+                "num_envs, stack and n_step must be at least 1",
+            ));
+        }
+        if capacity <= stack + n_step {
+            return Err(PyValueError::new_err("capacity must exceed stack + n_step"));
+        }
+        let config = ReplayConfig {
+            capacity,
+            stack,
+            n_step,
+            gamma,
+            priority_exponent,
+        };
+        Ok(PyReplayBuffer {
+            inner: ReplayBuffer::new(num_envs, frame_size, config, seed),
+            num_envs,
+            frame_size,
+        })
+    }
+
+    /// Starts new episodes from the results of `VecAtari.reset`.
+    fn reset(&mut self, results: Vec<PyStepResultArg>) -> PyResult<()> {
+        let results = self.step_results(results)?;
+        self.inner.reset(&results);
+        Ok(())
+    }
+
+    /// Adds the actions given to `VecAtari.step` and the results it returned.
+    fn add(&mut self, actions: Vec<i32>, results: Vec<PyStepResultArg>) -> PyResult<()> {
+        if actions.len() != self.num_envs {
+            return Err(PyValueError::new_err(format!(
+                "Expected {} actions, got {}",
+                self.num_envs,
+                actions.len()
+            )));
+        }
+        let actions = actions
+            .into_iter()
+            .map(to_action)
+            .collect::<PyResult<Vec<_>>>()?;
+        let results = self.step_results(results)?;
+        self.inner.add(&actions, &results);
+        Ok(())
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// (indices, obs, actions, returns, next_obs, discounts, weights); obs and next_obs are
+    /// bytes of shape (batch_size, stack, *frame). Raises ValueError when no transition can be
+    /// sampled yet.
+    #[pyo3(signature = (batch_size, beta=0.4))]
+    fn sample<'py>(
+        &mut self,
+        py: Python<'py>,
+        batch_size: usize,
+        beta: f64,
+    ) -> PyResult<PyBatch<'py>> {
+        let batch = self.inner.sample(batch_size, beta).ok_or_else(|| {
+            PyValueError::new_err("No transition in the replay buffer can be sampled yet")
+        })?;
+        Ok((
+            batch.indices,
+            PyBytes::new(py, &batch.obs),
+            batch.actions.into_iter().map(|a| a as i32).collect(),
+            batch.returns,
+            PyBytes::new(py, &batch.next_obs),
+            batch.discounts,
+            batch.weights,
+        ))
+    }
+
+    /// Number of transitions that can be sampled.
+    fn num_sampleable(&self) -> usize {
+        self.inner.num_sampleable()
+    }
+
+    fn update_priorities(&mut self, indices: Vec<usize>, priorities: Vec<f64>) -> PyResult<()> {
+        self.inner
+            .update_priorities(&indices, &priorities)
+            .map_err(PyValueError::new_err)
+    }
+}
+
+impl PyReplayBuffer {
+    fn step_results(&self, results: Vec<PyStepResultArg>) -> PyResult<Vec<StepResult>> {
+        if results.len() != self.num_envs || results.iter().any(|r| r.0 >= self.num_envs) {
+            return Err(PyValueError::new_err(format!(
+                "Expected one result for each of the {} envs",
+                self.num_envs
+            )));
+        }
+        if results.iter().any(|r| r.1.len() != self.frame_size) {
+            return Err(PyValueError::new_err(format!(
+                "Expected observations of {} bytes",
+                self.frame_size
+            )));
+        }
+        results.into_iter().map(tuple_to_step_result).collect()
+    }
+}
+
+#[pymethods]
+impl AleConfig {
+    #[new]
+    #[pyo3(signature = (
+        repeat_action_probability=0.0,
+        frame_skip=1,
+        color_averaging=false,
+        max_num_frames_per_episode=0,
+        mode=None,
+        difficulty=None,
+        truncate_on_loss_of_life=false,
+        full_action_space=false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_new(
+        repeat_action_probability: f32,
+        frame_skip: i32,
+        color_averaging: bool,
+        max_num_frames_per_episode: i32,
+        mode: Option<i32>,
+        difficulty: Option<i32>,
+        truncate_on_loss_of_life: bool,
+        full_action_space: bool,
+    ) -> PyResult<Self> {
+        let mut config = AleConfig::default();
+        config.repeat_action_probability = repeat_action_probability;
+        config.frame_skip = frame_skip;
+        config.color_averaging = color_averaging;
+        config.max_num_frames_per_episode = max_num_frames_per_episode;
+        config.mode = mode;
+        config.difficulty = difficulty;
+        config.truncate_on_loss_of_life = truncate_on_loss_of_life;
+        config.full_action_space = full_action_space;
+        config.validate().map_err(PyValueError::new_err)?;
+        Ok(config)
+    }
+
+    #[getter]
+    fn get_repeat_action_probability(&self) -> f32 {
+        self.repeat_action_probability
+    }
+
+    #[setter]
+    fn set_repeat_action_probability(&mut self, value: f32) {
+        self.repeat_action_probability = value;
+    }
+
+    #[getter]
+    fn get_frame_skip(&self) -> i32 {
+        self.frame_skip
+    }
+
+    #[setter]
+    fn set_frame_skip(&mut self, value: i32) {
+        self.frame_skip = value;
+    }
+
+    #[getter]
+    fn get_color_averaging(&self) -> bool {
+        self.color_averaging
+    }
+
+    #[setter]
+    fn set_color_averaging(&mut self, value: bool) {
+        self.color_averaging = value;
+    }
+
+    #[getter]
+    fn get_max_num_frames_per_episode(&self) -> i32 {
+        self.max_num_frames_per_episode
+    }
+
+    #[setter]
+    fn set_max_num_frames_per_episode(&mut self, value: i32) {
+        self.max_num_frames_per_episode = value;
+    }
+
+    #[getter]
+    fn get_mode(&self) -> Option<i32> {
+        self.mode
+    }
+
+    #[setter]
+    fn set_mode(&mut self, value: Option<i32>) {
+        self.mode = value;
+    }
+
+    #[getter]
+    fn get_difficulty(&self) -> Option<i32> {
+        self.difficulty
+    }
+
+    #[setter]
+    fn set_difficulty(&mut self, value: Option<i32>) {
+        self.difficulty = value;
+    }
+
+    #[getter]
+    fn get_truncate_on_loss_of_life(&self) -> bool {
+        self.truncate_on_loss_of_life
+    }
+
+    #[setter]
+    fn set_truncate_on_loss_of_life(&mut self, value: bool) {
+        self.truncate_on_loss_of_life = value;
+    }
+
+    #[getter]
+    fn get_full_action_space(&self) -> bool {
+        self.full_action_space
+    }
+
+    #[setter]
+    fn set_full_action_space(&mut self, value: bool) {
+        self.full_action_space = value;
+    }
+
+    #[pyo3(name = "set_int")]
+    fn py_set_int(&mut self, key: &str, value: i32) {
+        self.set_int(key, value)
+    }
+
+    #[pyo3(name = "set_float")]
+    fn py_set_float(&mut self, key: &str, value: f32) {
+        self.set_float(key, value)
+    }
+
+    #[pyo3(name = "set_bool")]
+    fn py_set_bool(&mut self, key: &str, value: bool) {
+        self.set_bool(key, value)
+    }
+
+    #[pyo3(name = "set_string")]
+    fn py_set_string(&mut self, key: &str, value: &str) {
+        self.set_string(key, value)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+#[pymethods]
+impl LoggerMode {
+    #[staticmethod]
+    #[pyo3(name = "from_logging_level")]
+    fn py_from_logging_level(level: i32) -> LoggerMode {
+        LoggerMode::from_logging_level(level)
+    }
+}
+
+#[pyfunction]
+fn set_logger_mode(mode: LoggerMode) {
+    logger::set_logger_mode(mode)
+}
+
+#[pyfunction]
+fn logger_mode() -> LoggerMode {
+    logger::logger_mode()
+}
+
+/// Names of the 18 ALE actions, indexed by action id.
+#[pyfunction]
+fn action_meanings() -> Vec<&'static str> {
+    Action::ALL.iter().map(|action| action.meaning()).collect()
+}
+
+/// Names of all bundled games, accepted by `Atari` and `VecAtari`.
+#[pyfunction]
+fn list_games() -> Vec<&'static str> {
+    BundledRom::iter().map(|rom| rom.name()).collect()
+}
+
+/// Metadata of a game as a dict, without loading its ROM.
+#[pyfunction]
+fn game_info<'py>(py: Python<'py>, game: &str) -> PyResult<Bound<'py, PyDict>> {
+    let rom = parse_game(game)?;
+    let info = rom
+        .info()
+        .ok_or_else(|| PyValueError::new_err(format!("No metadata for {}", rom)))?;
+    let dict = PyDict::new(py);
+    dict.set_item("name", rom.name())?;
+    dict.set_item("num_actions", info.num_actions)?;
+    dict.set_item("modes", info.modes)?;
+    dict.set_item("difficulties", info.difficulties)?;
+    dict.set_item("needs_fire", info.needs_fire)?;
+    dict.set_item("random_score", info.random_score)?;
+    dict.set_item("human_score", info.human_score)?;
+    Ok(dict)
+}
+
+fn parse_normalization(normalization: &str) -> PyResult<Normalization> {
+    match normalization {
+        "human" => Ok(Normalization::Human),
+        "record" => Ok(Normalization::Record),
+        "capped_human" => Ok(Normalization::CappedHuman),
+        _ => Err(PyValueError::new_err(format!(
+            "Unknown normalization {}, expected human, record or capped_human",
+            normalization
+        ))),
+    }
+}
+
+/// Normalizes a raw episode return of a game, `normalization` is human, record or capped_human.
+#[pyfunction]
+#[pyo3(signature = (game, score, normalization="human"))]
+fn normalized_score(game: &str, score: f64, normalization: &str) -> PyResult<f64> {
+    let rom = parse_game(game)?;
+    parse_normalization(normalization)?
+        .apply(rom, score)
+        .ok_or_else(|| PyValueError::new_err(format!("No reference scores for {}", rom)))
+}
+
+/// Normalizes raw returns, given as {game: [return of each run]}, and aggregates them into
+/// {metric: (estimate, lower, upper)} for the median, mean, iqm and optimality_gap.
+#[pyfunction]
+#[pyo3(signature = (returns, normalization="human", reps=2000, confidence=0.95, seed=0))]
+fn aggregate_scores(
+    returns: HashMap<String, Vec<f64>>,
+    normalization: &str,
+    reps: usize,
+    confidence: f64,
+    seed: u64,
+) -> PyResult<HashMap<&'static str, (f64, f64, f64)>> {
+    let normalization = parse_normalization(normalization)?;
+    let returns = returns
+        .into_iter()
+        .map(|(game, runs)| Ok((parse_game(&game)?, runs)))
+        .collect::<PyResult<Vec<_>>>()?;
+    let scores = normalization
+        .apply_all(&returns)
+        .ok_or_else(|| PyValueError::new_err("No reference scores for some of the games"))?;
+    Aggregate::ALL
+        .iter()
+        .map(|&aggregate| {
+            let ci = metrics::bootstrap_ci(&scores, aggregate, reps, confidence, seed)
+                .map_err(PyValueError::new_err)?;
+            Ok((aggregate.name(), (ci.estimate, ci.lower, ci.upper)))
+        })
+        .collect()
+}
+
+/// Episode written by `start_recording`, as a dict with its header entries and the bytes of
+/// its observations, of shape (steps + 1, height, width[, 3]).
+#[pyfunction]
+fn read_trajectory<'py>(py: Python<'py>, path: &str) -> PyResult<Bound<'py, PyDict>> {
+    let trajectory = Trajectory::read(path)?;
+    let dict = PyDict::new(py);
+    for (key, value) in &trajectory.settings {
+        dict.set_item(key, value)?;
+    }
+    dict.set_item("game", trajectory.game.name())?;
+    dict.set_item("seed", trajectory.seed)?;
+    dict.set_item("start", trajectory.start.name())?;
+    dict.set_item("env", trajectory.env)?;
+    dict.set_item("screen_dim", trajectory.screen_size)?;
+    dict.set_item("channels", trajectory.channels)?;
+    dict.set_item("obs", PyBytes::new(py, &trajectory.obs))?;
+    let actions: Vec<i32> = trajectory.actions.iter().map(|&a| a as i32).collect();
+    dict.set_item("actions", actions)?;
+    dict.set_item("rewards", trajectory.rewards)?;
+    dict.set_item("lives", trajectory.lives)?;
+    dict.set_item("terminals", trajectory.terminals)?;
+    dict.set_item("truncations", trajectory.truncations)?;
+    Ok(dict)
+}
+
+/// Writes an episode recorded with `start_recording` as a .gif, .png (APNG) or .y4m video.
+#[pyfunction]
+#[pyo3(signature = (trajectory_path, video_path, fps=60, scale=2, overlay=true))]
+fn trajectory_video(
+    trajectory_path: &str,
+    video_path: &str,
+    fps: u32,
+    scale: usize,
+    overlay: bool,
+) -> PyResult<()> {
+    let trajectory = Trajectory::read(trajectory_path)?;
+    let options = VideoOptions {
+        fps,
+        scale,
+        overlay,
+    };
+    Ok(video::trajectory_video(&trajectory, video_path, options)?)
+}
+
+/// Exports the trajectories recorded in a directory to a sharded dataset of .npy files, to be
+/// loaded with numpy; returns the number of episodes.
+#[pyfunction]
+#[pyo3(signature = (trajectory_dir, dataset_dir, shard_steps=100_000))]
+fn export_dataset(trajectory_dir: &str, dataset_dir: &str, shard_steps: usize) -> PyResult<usize> {
+    let options = DatasetOptions { shard_steps };
+    Ok(dataset::export_dataset(
+        trajectory_dir,
+        dataset_dir,
+        options,
+    )?)
+}
+
+/// Replays an action log saved by `Atari.save_action_log` and returns its score; raises
+/// RuntimeError at the first step that differs from the log, and ValueError when its header
+/// has invalid settings.
+#[pyfunction]
+fn replay_action_log(path: &str) -> PyResult<i32> {
+    let log = ActionLog::load(path)?;
+    log.replay().map_err(|e| match e {
+        ReplayMismatch::Header { .. } => PyValueError::new_err(e.to_string()),
+        _ => PyRuntimeError::new_err(e.to_string()),
+    })
+}
+
+/// Identifiers of the named benchmark suites.
+#[pyfunction]
+fn list_suites() -> Vec<&'static str> {
+    Suite::ALL.iter().map(|suite| suite.name()).collect()
+}
+
+/// Games of a named benchmark suite such as "atari-5" or "atari-100k".
+#[pyfunction]
+fn suite_games(suite: &str) -> PyResult<Vec<&'static str>> {
+    let suite: Suite = suite.parse().map_err(PyValueError::new_err)?;
+    Ok(suite.games().iter().map(|rom| rom.name()).collect())
+}
+
+#[pyfunction]
+fn derive_seed(seed: i32, index: usize) -> i32 {
+    seeding::derive_seed(seed, index)
+}
+
+// called by the package on import, so that worker processes run `python -m ale_env._worker`
+#[cfg(unix)]
+#[pyfunction]
+fn _set_worker_command(program: &str, args: Vec<String>) {
+    process_vec_atari::set_worker_command(program, args.into_iter().map(Into::into).collect());
+}
+
+// body of a worker process of the processes backend, never returns
+#[cfg(unix)]
+#[pyfunction]
+fn _worker_main() {
+    process_vec_atari::worker_main()
+}
+
+#[pymodule]
+mod ale_env {
+    #[cfg(unix)]
+    #[pymodule_export]
+    use super::_set_worker_command;
+    #[cfg(unix)]
+    #[pymodule_export]
+    use super::_worker_main;
+    #[pymodule_export]
+    use super::action_meanings;
+    #[pymodule_export]
+    use super::aggregate_scores;
+    #[pymodule_export]
+    use super::derive_seed;
+    #[pymodule_export]
+    use super::export_dataset;
+    #[pymodule_export]
+    use super::game_info;
+    #[pymodule_export]
+    use super::list_games;
+    #[pymodule_export]
+    use super::list_suites;
+    #[pymodule_export]
+    use super::logger_mode;
+    #[pymodule_export]
+    use super::normalized_score;
+    #[pymodule_export]
+    use super::read_trajectory;
+    #[pymodule_export]
+    use super::replay_action_log;
+    #[pymodule_export]
+    use super::set_logger_mode;
+    #[pymodule_export]
+    use super::suite_games;
+    #[pymodule_export]
+    use super::trajectory_video;
+    #[pymodule_export]
+    use super::AleConfig;
+    #[pymodule_export]
+    use super::LoggerMode;
+    #[pymodule_export]
+    use super::PyAtari;
+    #[cfg(unix)]
+    #[pymodule_export]
+    use super::PyObsRing;
+    #[pymodule_export]
+    use super::PyReplayBuffer;
+    #[pymodule_export]
+    use super::PyVecAtari;
+}
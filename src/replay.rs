@@ -0,0 +1,623 @@
+//! Replay buffer fed from `VecAtari` step results, storing every frame once.
+//!
+//! Each env gets a circular buffer of `capacity` time slots holding the observation at that
+//! timestep together with the action taken from it and the resulting reward and episode end.
+//! Frame stacks are rebuilt when sampling; frames from before the start of an episode are
+//! zeros. Because `VecAtari` resets finished envs within the step, the slot after the last
+//! step of an episode holds the first observation of the next one. Terminal transitions do not
+//! need the last observation; for truncations it is kept aside from `StepResult::final_obs`,
+//! so that windows reaching a truncation bootstrap from it. Windows reaching a truncation
+//! without a final observation are not sampled.
+
+use crate::action::Action;
+use crate::vec_atari::StepResult;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+// prioritized draws in a row that may land on unsampleable slots before drawing uniformly,
+// e.g. once all priorities underflowed to 0
+const MAX_REJECTIONS: usize = 100;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayConfig {
+    /// Time slots kept per env.
+    pub capacity: usize,
+    /// Frames stacked into one observation.
+    pub stack: usize,
+    /// Rewards summed into a return before bootstrapping.
+    pub n_step: usize,
+    pub gamma: f32,
+    /// Exponent of prioritized replay (Schaul et al., 2016); `None` samples uniformly.
+    pub priority_exponent: Option<f64>,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        ReplayConfig {
+            capacity: 100_000,
+            stack: 4,
+            n_step: 1,
+            gamma: 0.99,
+            priority_exponent: None,
+        }
+    }
+}
+
+/// Sampled transitions; observations are `[batch][stack][frame]` bytes.
+#[derive(Clone, Debug, Default)]
+pub struct Batch {
+    /// Slots of the transitions, for `ReplayBuffer::update_priorities`.
+    pub indices: Vec<usize>,
+    pub obs: Vec<u8>,
+    pub actions: Vec<Action>,
+    /// Discounted sum of the next `n_step` rewards, cut at the end of the episode.
+    pub returns: Vec<f32>,
+    pub next_obs: Vec<u8>,
+    /// `gamma^n_step` to apply to the value of `next_obs`, 0 when the episode terminated. When
+    /// the window reaches a truncation, `next_obs` ends with the last observation of the
+    /// episode.
+    pub discounts: Vec<f32>,
+    /// Importance sampling weights `(N * P(i))^-beta` over the `N` sampleable transitions,
+    /// normalized by their maximum; all 1 for uniform sampling.
+    pub weights: Vec<f32>,
+}
+
+pub struct ReplayBuffer {
+    config: ReplayConfig,
+    num_envs: usize,
+    frame_size: usize,
+    // per slot, indexed by env * capacity + t % capacity
+    frames: Vec<u8>,
+    actions: Vec<Action>,
+    rewards: Vec<f32>,
+    terminals: Vec<bool>,
+    truncations: Vec<bool>,
+    // last observation of the episode truncated by the action of a slot
+    final_frames: HashMap<usize, Vec<u8>>,
+    firsts: Vec<bool>,
+    // whether the transition of a slot has a complete n-step window and frame stack; only
+    // these have a priority
+    sampleable: Vec<bool>,
+    num_sampleable: usize,
+    // timestep of the slot of each env whose action is not known yet
+    heads: Vec<u64>,
+    started: bool,
+    priorities: Option<SumTree>,
+    max_priority: f64,
+    rng: StdRng,
+}
+
+impl ReplayBuffer {
+    /// Buffer for `num_envs` envs whose observations are `frame_size` bytes.
+    pub fn new(num_envs: usize, frame_size: usize, config: ReplayConfig, seed: u64) -> Self {
+        assert!(num_envs > 0, "Replay buffer needs at least one env");
+        assert!(
+            config.stack > 0 && config.n_step > 0,
+            "stack and n_step must be at least 1"
+        );
+        assert!(
+            config.capacity > config.stack + config.n_step,
+            "capacity must exceed stack + n_step"
+        );
+        let slots = num_envs * config.capacity;
+        ReplayBuffer {
+            priorities: config.priority_exponent.map(|_| SumTree::new(slots)),
+            num_envs,
+            frame_size,
+            frames: vec![0u8; slots * frame_size],
+            actions: vec![Action::Noop; slots],
+            rewards: vec![0.0; slots],
+            terminals: vec![false; slots],
+            truncations: vec![false; slots],
+            final_frames: HashMap::new(),
+            firsts: vec![false; slots],
+            sampleable: vec![false; slots],
+            num_sampleable: 0,
+            heads: vec![0; num_envs],
+            started: false,
+            max_priority: 1.0,
+            rng: StdRng::seed_from_u64(seed),
+            config,
+        }
+    }
+
+    /// Starts new episodes from the results of `VecAtari::reset`.
+    pub fn reset(&mut self, results: &[StepResult]) {
+        assert_eq!(results.len(), self.num_envs, "Expected one result per env");
+        for result in results {
+            let head = self.heads[result.index];
+            // an episode cut short by the reset is truncated, its last observation is the one
+            // the reset overwrites
+            let slot = self.slot(result.index, head);
+            if self.started && head > 0 {
+                let last = self.slot(result.index, head - 1);
+                if !self.terminals[last] && !self.truncations[last] {
+                    self.truncations[last] = true;
+                    let frame =
+                        self.frames[slot * self.frame_size..(slot + 1) * self.frame_size].to_vec();
+                    self.final_frames.insert(last, frame);
+                }
+                self.refresh(result.index, head.saturating_sub(self.config.n_step as u64));
+            }
+            self.write_frame(slot, &result.obs, true);
+        }
+        self.started = true;
+    }
+
+    /// Adds the actions given to `VecAtari::step` and the results it returned.
+    pub fn add(&mut self, actions: &[Action], results: &[StepResult]) {
+        assert!(self.started, "Reset the replay buffer before adding steps");
+        assert_eq!(results.len(), self.num_envs, "Expected one result per env");
+        assert_eq!(actions.len(), self.num_envs, "Expected one action per env");
+        for (result, &action) in results.iter().zip(actions) {
+            let env = result.index;
+            let slot = self.slot(env, self.heads[env]);
+            self.actions[slot] = action;
+            self.rewards[slot] = result.reward as f32;
+            self.terminals[slot] = result.terminal;
+            self.truncations[slot] = result.truncation;
+            if let Some(last) = result.final_obs.as_ref().filter(|_| result.truncation) {
+                assert_eq!(
+                    last.len(),
+                    self.frame_size,
+                    "Observation has the wrong size"
+                );
+                self.final_frames.insert(slot, last.clone());
+            }
+
+            // the observation of a finished env is the first one of its next episode
+            self.heads[env] += 1;
+            let next = self.slot(env, self.heads[env]);
+            self.write_frame(next, &result.obs, result.terminal || result.truncation);
+
+            // windows reaching this step may be complete now, and the oldest stored transition
+            // may have lost the first frame of its stack
+            let head = self.heads[env];
+            self.refresh(env, head.saturating_sub(self.config.n_step as u64));
+            let oldest = self.oldest(env);
+            if oldest > 0 {
+                self.set_sampleable(self.slot(env, oldest - 1), false);
+            }
+        }
+    }
+
+    pub fn num_envs(&self) -> usize {
+        self.num_envs
+    }
+
+    /// Number of completed transitions held, including those not sampleable yet.
+    pub fn len(&self) -> usize {
+        self.heads
+            .iter()
+            .map(|&head| head.min(self.config.capacity as u64 - 1) as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of transitions that can be sampled.
+    pub fn num_sampleable(&self) -> usize {
+        self.num_sampleable
+    }
+
+    /// Samples `batch_size` transitions; `beta` is the importance sampling exponent of
+    /// prioritized replay and is ignored by uniform sampling. None when no transition can be
+    /// sampled yet.
+    pub fn sample(&mut self, batch_size: usize, beta: f64) -> Option<Batch> {
+        if self.num_sampleable == 0 {
+            return None;
+        }
+        let stack_size = self.config.stack * self.frame_size;
+        let mut batch = Batch {
+            obs: vec![0u8; batch_size * stack_size],
+            next_obs: vec![0u8; batch_size * stack_size],
+            ..Batch::default()
+        };
+        let mut rejections = 0;
+        while batch.indices.len() < batch_size {
+            let prioritized = self
+                .priorities
+                .as_ref()
+                .filter(|tree| tree.total() > 0.0 && rejections < MAX_REJECTIONS);
+            let (env, t, weight) = match prioritized {
+                Some(tree) => {
+                    let slot = tree.find(self.rng.gen::<f64>() * tree.total());
+                    if !self.sampleable[slot] {
+                        rejections += 1;
+                        continue;
+                    }
+                    let env = slot / self.config.capacity;
+                    let probability = tree.get(slot) / tree.total();
+                    let weight = (self.num_sampleable as f64 * probability).powf(-beta);
+                    (env, self.time_of(slot), weight as f32)
+                }
+                None => {
+                    let env = self.rng.gen_range(0..self.num_envs);
+                    let (oldest, head) = (self.oldest(env), self.heads[env]);
+                    if oldest >= head {
+                        continue;
+                    }
+                    (env, self.rng.gen_range(oldest..head), 1.0)
+                }
+            };
+            let Some((steps, end)) = self.window(env, t) else {
+                continue;
+            };
+            rejections = 0;
+
+            let i = batch.indices.len();
+            let (mut ret, mut discount) = (0.0, 1.0);
+            for k in 0..steps {
+                ret += discount * self.rewards[self.slot(env, t + k)];
+                discount *= self.config.gamma;
+            }
+            self.write_stack(env, t, &mut batch.obs[i * stack_size..(i + 1) * stack_size]);
+            let next_obs = &mut batch.next_obs[i * stack_size..(i + 1) * stack_size];
+            match end {
+                End::Bootstrap => self.write_stack(env, t + steps, next_obs),
+                End::Terminal => self.write_stack(env, t, next_obs),
+                End::Truncation => {
+                    // the stack of the last step, shifted by the last observation
+                    let last = self.slot(env, t + steps - 1);
+                    self.write_stack(env, t + steps - 1, next_obs);
+                    next_obs.copy_within(self.frame_size.., 0);
+                    next_obs[stack_size - self.frame_size..]
+                        .copy_from_slice(&self.final_frames[&last]);
+                }
+            }
+            batch.indices.push(self.slot(env, t));
+            batch.actions.push(self.actions[self.slot(env, t)]);
+            batch.returns.push(ret);
+            batch.discounts.push(match end {
+                End::Terminal => 0.0,
+                End::Bootstrap | End::Truncation => discount,
+            });
+            batch.weights.push(weight);
+        }
+        let max_weight = batch.weights.iter().copied().fold(0.0, f32::max);
+        if max_weight > 0.0 {
+            batch.weights.iter_mut().for_each(|w| *w /= max_weight);
+        }
+        Some(batch)
+    }
+
+    /// Sets the priorities of sampled transitions, e.g. to their absolute TD errors. An index
+    /// out of range is an error, and no priority is changed then.
+    pub fn update_priorities(
+        &mut self,
+        indices: &[usize],
+        priorities: &[f64],
+    ) -> Result<(), String> {
+        if indices.len() != priorities.len() {
+            return Err(format!(
+                "Expected one priority per index, got {} indices and {} priorities",
+                indices.len(),
+                priorities.len()
+            ));
+        }
+        if let Some(&slot) = indices.iter().find(|&&slot| slot >= self.sampleable.len()) {
+            return Err(format!(
+                "Index {} is out of range for {} slots",
+                slot,
+                self.sampleable.len()
+            ));
+        }
+        let exponent = self.config.priority_exponent;
+        let (Some(tree), Some(exponent)) = (self.priorities.as_mut(), exponent) else {
+            return Ok(());
+        };
+        for (&slot, &priority) in indices.iter().zip(priorities) {
+            let priority = priority.max(1e-6).powf(exponent);
+            self.max_priority = self.max_priority.max(priority);
+            // the slot may have been rewritten since it was sampled
+            if self.sampleable[slot] {
+                tree.set(slot, priority);
+            }
+        }
+        Ok(())
+    }
+
+    fn slot(&self, env: usize, t: u64) -> usize {
+        env * self.config.capacity + (t % self.config.capacity as u64) as usize
+    }
+
+    // timestep currently stored in a slot
+    fn time_of(&self, slot: usize) -> u64 {
+        let capacity = self.config.capacity as u64;
+        let head = self.heads[slot / self.config.capacity];
+        let behind = (head % capacity + capacity - (slot % self.config.capacity) as u64) % capacity;
+        head.saturating_sub(behind)
+    }
+
+    // oldest timestep whose frame stack is still fully stored
+    fn oldest(&self, env: usize) -> u64 {
+        let stored = (self.heads[env] + 1).saturating_sub(self.config.capacity as u64);
+        stored + self.config.stack as u64 - 1
+    }
+
+    // steps of the n-step window of a transition and how it ends; None when the transition is
+    // incomplete, evicted or reaches a truncation without a last observation
+    fn window(&self, env: usize, t: u64) -> Option<(u64, End)> {
+        let head = self.heads[env];
+        if t < self.oldest(env) || t >= head {
+            return None;
+        }
+        for k in 0..self.config.n_step as u64 {
+            if t + k >= head {
+                return None;
+            }
+            let slot = self.slot(env, t + k);
+            if self.terminals[slot] {
+                return Some((k + 1, End::Terminal));
+            }
+            if self.truncations[slot] {
+                return match self.final_frames.contains_key(&slot) {
+                    true => Some((k + 1, End::Truncation)),
+                    false => None,
+                };
+            }
+        }
+        Some((self.config.n_step as u64, End::Bootstrap))
+    }
+
+    fn write_frame(&mut self, slot: usize, obs: &[u8], first: bool) {
+        assert_eq!(obs.len(), self.frame_size, "Observation has the wrong size");
+        self.frames[slot * self.frame_size..(slot + 1) * self.frame_size].copy_from_slice(obs);
+        self.firsts[slot] = first;
+        self.terminals[slot] = false;
+        self.truncations[slot] = false;
+        self.final_frames.remove(&slot);
+        self.set_sampleable(slot, false);
+    }
+
+    // update whether the transitions of env from timestep from to its head can be sampled
+    fn refresh(&mut self, env: usize, from: u64) {
+        for t in from..self.heads[env] {
+            let sampleable = self.window(env, t).is_some();
+            self.set_sampleable(self.slot(env, t), sampleable);
+        }
+    }
+
+    // transitions get the maximum priority when they become sampleable, and 0 when they stop
+    fn set_sampleable(&mut self, slot: usize, sampleable: bool) {
+        if self.sampleable[slot] == sampleable {
+            return;
+        }
+        self.sampleable[slot] = sampleable;
+        match sampleable {
+            true => self.num_sampleable += 1,
+            false => self.num_sampleable -= 1,
+        }
+        let priority = if sampleable { self.max_priority } else { 0.0 };
+        if let Some(tree) = self.priorities.as_mut() {
+            tree.set(slot, priority);
+        }
+    }
+
+    // frames t - stack + 1 ..= t of env, zeros before the start of the episode
+    fn write_stack(&self, env: usize, t: u64, out: &mut [u8]) {
+        out.fill(0);
+        let stack = self.config.stack as u64;
+        for j in 0..stack {
+            let slot = self.slot(env, t - j);
+            let k = (stack - 1 - j) as usize;
+            out[k * self.frame_size..(k + 1) * self.frame_size].copy_from_slice(
+                &self.frames[slot * self.frame_size..(slot + 1) * self.frame_size],
+            );
+            if self.firsts[slot] {
+                break;
+            }
+        }
+    }
+}
+
+// how the n-step window of a transition ends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum End {
+    // after n_step steps, bootstrapping from the stack of the next timestep
+    Bootstrap,
+    Terminal,
+    // bootstrapping from the last observation of the episode
+    Truncation,
+}
+
+// binary tree whose inner nodes hold the sum of the priorities below them
+struct SumTree {
+    leaves: usize,
+    nodes: Vec<f64>,
+}
+
+impl SumTree {
+    fn new(size: usize) -> SumTree {
+        let leaves = size.next_power_of_two();
+        SumTree {
+            leaves,
+            nodes: vec![0.0; 2 * leaves],
+        }
+    }
+
+    fn set(&mut self, i: usize, priority: f64) {
+        let mut node = i + self.leaves;
+        self.nodes[node] = priority;
+        while node > 1 {
+            node /= 2;
+            self.nodes[node] = self.nodes[2 * node] + self.nodes[2 * node + 1];
+        }
+    }
+
+    fn get(&self, i: usize) -> f64 {
+        self.nodes[i + self.leaves]
+    }
+
+    fn total(&self) -> f64 {
+        self.nodes[1]
+    }
+
+    // leaf at which the running sum of priorities exceeds mass
+    fn find(&self, mut mass: f64) -> usize {
+        let mut node = 1;
+        while node < self.leaves {
+            let left = self.nodes[2 * node];
+            node = match mass < left {
+                true => 2 * node,
+                false => {
+                    mass -= left;
+                    2 * node + 1
+                }
+            };
+        }
+        node - self.leaves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom::BundledRom;
+
+    fn buffer(stack: usize, n_step: usize) -> ReplayBuffer {
+        let config = ReplayConfig {
+            capacity: 20,
+            stack,
+            n_step,
+            gamma: 0.5,
+            priority_exponent: None,
+        };
+        ReplayBuffer::new(1, 1, config, 0)
+    }
+
+    fn result(obs: u8, terminal: bool, truncation: bool) -> StepResult {
+        StepResult {
+            index: 0,
+            game: BundledRom::Pong,
+            obs: vec![obs],
+            reward: 1,
+            terminal,
+            truncation,
+            life_loss: false,
+            score: None,
+            final_obs: None,
+        }
+    }
+
+    #[test]
+    fn sum_tree_finds_leaves_by_mass() {
+        let mut tree = SumTree::new(5);
+        for (i, priority) in [1.0, 2.0, 0.0, 3.0, 4.0].into_iter().enumerate() {
+            tree.set(i, priority);
+        }
+        assert_eq!(tree.total(), 10.0);
+        assert_eq!(tree.find(0.5), 0);
+        assert_eq!(tree.find(1.0), 1);
+        // leaves without priority are never found
+        assert_eq!(tree.find(3.0), 3);
+        assert_eq!(tree.find(5.9), 3);
+        assert_eq!(tree.find(6.0), 4);
+        assert_eq!(tree.find(9.9), 4);
+
+        tree.set(3, 0.0);
+        assert_eq!(tree.get(3), 0.0);
+        assert_eq!(tree.total(), 7.0);
+        assert_eq!(tree.find(3.0), 4);
+    }
+
+    #[test]
+    fn frame_stacks_are_zero_before_the_episode_start() {
+        let mut buffer = buffer(3, 1);
+        buffer.reset(&[result(1, false, false)]);
+        // the episode ends with the action from timestep 1, timestep 2 starts the next one
+        for (obs, terminal) in [(2, false), (10, true), (11, false), (12, false)] {
+            buffer.add(&[Action::Fire], &[result(obs, terminal, false)]);
+        }
+        let stack = |t: u64| {
+            let mut out = [0u8; 3];
+            buffer.write_stack(0, t, &mut out);
+            out
+        };
+        assert_eq!(stack(1), [0, 1, 2]);
+        assert_eq!(stack(2), [0, 0, 10]);
+        assert_eq!(stack(3), [0, 10, 11]);
+        assert_eq!(stack(4), [10, 11, 12]);
+    }
+
+    #[test]
+    fn n_step_windows_stop_at_terminals_and_truncations() {
+        let mut buffer = buffer(1, 3);
+        buffer.reset(&[result(0, false, false)]);
+        // the action from timestep 1 terminates an episode and the one from timestep 4
+        // truncates the next
+        for t in 0..9u8 {
+            buffer.add(&[Action::Fire], &[result(t + 1, t == 1, t == 4)]);
+        }
+        assert_eq!(buffer.window(0, 0), Some((2, End::Terminal)));
+        assert_eq!(buffer.window(0, 1), Some((1, End::Terminal)));
+        // the truncation has no last observation to bootstrap from
+        for t in 2..=4 {
+            assert_eq!(buffer.window(0, t), None);
+        }
+        assert_eq!(buffer.window(0, 5), Some((3, End::Bootstrap)));
+        assert_eq!(buffer.window(0, 6), Some((3, End::Bootstrap)));
+        // incomplete windows
+        assert_eq!(buffer.window(0, 7), None);
+        assert_eq!(buffer.window(0, 8), None);
+        assert_eq!(buffer.num_sampleable(), 4);
+    }
+
+    #[test]
+    fn truncated_windows_bootstrap_from_the_last_observation() {
+        let mut buffer = buffer(2, 2);
+        buffer.reset(&[result(1, false, false)]);
+        // the action from timestep 2 truncates the episode, whose last observation is 30
+        buffer.add(&[Action::Fire], &[result(2, false, false)]);
+        buffer.add(&[Action::Fire], &[result(3, false, false)]);
+        let mut truncated = result(10, false, true);
+        truncated.final_obs = Some(vec![30]);
+        buffer.add(&[Action::Fire], &[truncated]);
+        buffer.add(&[Action::Fire], &[result(11, false, false)]);
+        assert_eq!(buffer.window(0, 1), Some((2, End::Truncation)));
+        assert_eq!(buffer.window(0, 2), Some((1, End::Truncation)));
+
+        let batch = buffer.sample(64, 0.0).unwrap();
+        for (i, &slot) in batch.indices.iter().enumerate() {
+            let (next_obs, discount) = (&batch.next_obs[2 * i..2 * i + 2], batch.discounts[i]);
+            match slot {
+                1 => assert_eq!((next_obs, discount), (&[3, 30][..], 0.25)),
+                2 => assert_eq!((next_obs, discount), (&[3, 30][..], 0.5)),
+                _ => panic!("slot {} is not sampleable", slot),
+            }
+        }
+
+        // a reset cuts the next episode short and overwrites its last observation 12
+        buffer.add(&[Action::Fire], &[result(12, false, false)]);
+        buffer.reset(&[result(20, false, false)]);
+        assert_eq!(buffer.window(0, 4), Some((1, End::Truncation)));
+        assert_eq!(buffer.final_frames[&4], [12]);
+    }
+
+    #[test]
+    fn underflowed_priorities_fall_back_to_uniform_sampling() {
+        let config = ReplayConfig {
+            capacity: 20,
+            stack: 1,
+            n_step: 1,
+            gamma: 0.5,
+            priority_exponent: Some(100.0),
+        };
+        let mut buffer = ReplayBuffer::new(1, 1, config, 0);
+        buffer.reset(&[result(0, false, false)]);
+        for t in 0..10 {
+            buffer.add(&[Action::Fire], &[result(t + 1, false, false)]);
+        }
+        let indices: Vec<usize> = (0..10).collect();
+        buffer.update_priorities(&indices, &[0.0; 10]).unwrap();
+        assert_eq!(buffer.priorities.as_ref().unwrap().total(), 0.0);
+
+        let batch = buffer.sample(16, 0.4).unwrap();
+        assert_eq!(batch.indices.len(), 16);
+        assert!(batch.indices.iter().all(|&slot| buffer.sampleable[slot]));
+        assert!(batch.weights.iter().all(|w| w.is_finite()));
+    }
+}
@@ -0,0 +1,617 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BundledRom {
+    Adventure,
+    AirRaid,
+    Alien,
+    Amidar,
+    Assault,
+    Asterix,
+    Asteroids,
+    Atlantis,
+    Atlantis2,
+    Backgammon,
+    BankHeist,
+    BasicMath,
+    BattleZone,
+    BeamRider,
+    Berzerk,
+    Blackjack,
+    Bowling,
+    Boxing,
+    Breakout,
+    Carnival,
+    Casino,
+    Centipede,
+    ChopperCommand,
+    Combat,
+    CrazyClimber,
+    Crossbow,
+    Darkchambers,
+    Defender,
+    DemonAttack,
+    DonkeyKong,
+    DoubleDunk,
+    Earthworld,
+    ElevatorAction,
+    Enduro,
+    Entombed,
+    Et,
+    FishingDerby,
+    FlagCapture,
+    Freeway,
+    Frogger,
+    Frostbite,
+    Galaxian,
+    Gopher,
+    Gravitar,
+    Hangman,
+    HauntedHouse,
+    Hero,
+    HumanCannonball,
+    IceHockey,
+    Jamesbond,
+    JourneyEscape,
+    Joust,
+    Kaboom,
+    Kangaroo,
+    KeystoneKapers,
+    KingKong,
+    Klax,
+    Koolaid,
+    Krull,
+    KungFuMaster,
+    LaserGates,
+    LostLuggage,
+    MarioBros,
+    MazeCraze,
+    MiniatureGolf,
+    MontezumaRevenge,
+    MrDo,
+    MsPacman,
+    NameThisGame,
+    Othello,
+    Pacman,
+    Phoenix,
+    Pitfall,
+    Pitfall2,
+    Pong,
+    Pooyan,
+    PrivateEye,
+    Qbert,
+    Riverraid,
+    RoadRunner,
+    Robotank,
+    Seaquest,
+    SirLancelot,
+    Skiing,
+    Solaris,
+    SpaceInvaders,
+    SpaceWar,
+    StarGunner,
+    Superman,
+    Surround,
+    Tennis,
+    Tetris,
+    TicTacToe3d,
+    TimePilot,
+    Trondead,
+    Turmoil,
+    Tutankham,
+    UpNDown,
+    Venture,
+    VideoCheckers,
+    VideoChess,
+    VideoCube,
+    VideoPinball,
+    Warlords,
+    WizardOfWor,
+    WordZapper,
+    YarsRevenge,
+    Zaxxon,
+}
+
+/// Error returned when a game name matches no bundled ROM.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseRomError {
+    pub name: String,
+    /// Closest supported game, if any is close enough to be a likely typo.
+    pub suggestion: Option<BundledRom>,
+}
+
+impl fmt::Display for ParseRomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ROM {} not supported", self.name)?;
+        if let Some(rom) = self.suggestion {
+            write!(f, ", did you mean {}?", rom)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseRomError {}
+
+impl BundledRom {
+    /// Every bundled ROM, in alphabetical order.
+    pub const ALL: [BundledRom; 108] = [
+        BundledRom::Adventure,
+        BundledRom::AirRaid,
+        BundledRom::Alien,
+        BundledRom::Amidar,
+        BundledRom::Assault,
+        BundledRom::Asterix,
+        BundledRom::Asteroids,
+        BundledRom::Atlantis,
+        BundledRom::Atlantis2,
+        BundledRom::Backgammon,
+        BundledRom::BankHeist,
+        BundledRom::BasicMath,
+        BundledRom::BattleZone,
+        BundledRom::BeamRider,
+        BundledRom::Berzerk,
+        BundledRom::Blackjack,
+        BundledRom::Bowling,
+        BundledRom::Boxing,
+        BundledRom::Breakout,
+        BundledRom::Carnival,
+        BundledRom::Casino,
+        BundledRom::Centipede,
+        BundledRom::ChopperCommand,
+        BundledRom::Combat,
+        BundledRom::CrazyClimber,
+        BundledRom::Crossbow,
+        BundledRom::Darkchambers,
+        BundledRom::Defender,
+        BundledRom::DemonAttack,
+        BundledRom::DonkeyKong,
+        BundledRom::DoubleDunk,
+        BundledRom::Earthworld,
+        BundledRom::ElevatorAction,
+        BundledRom::Enduro,
+        BundledRom::Entombed,
+        BundledRom::Et,
+        BundledRom::FishingDerby,
+        BundledRom::FlagCapture,
+        BundledRom::Freeway,
+        BundledRom::Frogger,
+        BundledRom::Frostbite,
+        BundledRom::Galaxian,
+        BundledRom::Gopher,
+        BundledRom::Gravitar,
+        BundledRom::Hangman,
+        BundledRom::HauntedHouse,
+        BundledRom::Hero,
+        BundledRom::HumanCannonball,
+        BundledRom::IceHockey,
+        BundledRom::Jamesbond,
+        BundledRom::JourneyEscape,
+        BundledRom::Joust,
+        BundledRom::Kaboom,
+        BundledRom::Kangaroo,
+        BundledRom::KeystoneKapers,
+        BundledRom::KingKong,
+        BundledRom::Klax,
+        BundledRom::Koolaid,
+        BundledRom::Krull,
+        BundledRom::KungFuMaster,
+        BundledRom::LaserGates,
+        BundledRom::LostLuggage,
+        BundledRom::MarioBros,
+        BundledRom::MazeCraze,
+        BundledRom::MiniatureGolf,
+        BundledRom::MontezumaRevenge,
+        BundledRom::MrDo,
+        BundledRom::MsPacman,
+        BundledRom::NameThisGame,
+        BundledRom::Othello,
+        BundledRom::Pacman,
+        BundledRom::Phoenix,
+        BundledRom::Pitfall,
+        BundledRom::Pitfall2,
+        BundledRom::Pong,
+        BundledRom::Pooyan,
+        BundledRom::PrivateEye,
+        BundledRom::Qbert,
+        BundledRom::Riverraid,
+        BundledRom::RoadRunner,
+        BundledRom::Robotank,
+        BundledRom::Seaquest,
+        BundledRom::SirLancelot,
+        BundledRom::Skiing,
+        BundledRom::Solaris,
+        BundledRom::SpaceInvaders,
+        BundledRom::SpaceWar,
+        BundledRom::StarGunner,
+        BundledRom::Superman,
+        BundledRom::Surround,
+        BundledRom::Tennis,
+        BundledRom::Tetris,
+        BundledRom::TicTacToe3d,
+        BundledRom::TimePilot,
+        BundledRom::Trondead,
+        BundledRom::Turmoil,
+        BundledRom::Tutankham,
+        BundledRom::UpNDown,
+        BundledRom::Venture,
+        BundledRom::VideoCheckers,
+        BundledRom::VideoChess,
+        BundledRom::VideoCube,
+        BundledRom::VideoPinball,
+        BundledRom::Warlords,
+        BundledRom::WizardOfWor,
+        BundledRom::WordZapper,
+        BundledRom::YarsRevenge,
+        BundledRom::Zaxxon,
+    ];
+
+    pub fn iter() -> impl Iterator<Item = BundledRom> {
+        BundledRom::ALL.into_iter()
+    }
+
+    pub fn name2rom(name: &str) -> BundledRom {
+        name.parse().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Snake case name of the game, e.g. `"montezuma_revenge"`.
+    pub fn name(&self) -> &'static str {
+        use BundledRom::*;
+        match self {
+            Adventure => "adventure",
+            AirRaid => "air_raid",
+            Alien => "alien",
+            Amidar => "amidar",
+            Assault => "assault",
+            Asterix => "asterix",
+            Asteroids => "asteroids",
+            Atlantis => "atlantis",
+            Atlantis2 => "atlantis2",
+            Backgammon => "backgammon",
+            BankHeist => "bank_heist",
+            BasicMath => "basic_math",
+            BattleZone => "battle_zone",
+            BeamRider => "beam_rider",
+            Berzerk => "berzerk",
+            Blackjack => "blackjack",
+            Bowling => "bowling",
+            Boxing => "boxing",
+            Breakout => "breakout",
+            Carnival => "carnival",
+            Casino => "casino",
+            Centipede => "centipede",
+            ChopperCommand => "chopper_command",
+            Combat => "combat",
+            CrazyClimber => "crazy_climber",
+            Crossbow => "crossbow",
+            Darkchambers => "darkchambers",
+            Defender => "defender",
+            DemonAttack => "demon_attack",
+            DonkeyKong => "donkey_kong",
+            DoubleDunk => "double_dunk",
+            Earthworld => "earthworld",
+            ElevatorAction => "elevator_action",
+            Enduro => "enduro",
+            Entombed => "entombed",
+            Et => "et",
+            FishingDerby => "fishing_derby",
+            FlagCapture => "flag_capture",
+            Freeway => "freeway",
+            Frogger => "frogger",
+            Frostbite => "frostbite",
+            Galaxian => "galaxian",
+            Gopher => "gopher",
+            Gravitar => "gravitar",
+            Hangman => "hangman",
+            HauntedHouse => "haunted_house",
+            Hero => "hero",
+            HumanCannonball => "human_cannonball",
+            IceHockey => "ice_hockey",
+            Jamesbond => "jamesbond",
+            JourneyEscape => "journey_escape",
+            Joust => "joust",
+            Kaboom => "kaboom",
+            Kangaroo => "kangaroo",
+            KeystoneKapers => "keystone_kapers",
+            KingKong => "king_kong",
+            Klax => "klax",
+            Koolaid => "koolaid",
+            Krull => "krull",
+            KungFuMaster => "kung_fu_master",
+            LaserGates => "laser_gates",
+            LostLuggage => "lost_luggage",
+            MarioBros => "mario_bros",
+            MazeCraze => "maze_craze",
+            MiniatureGolf => "miniature_golf",
+            MontezumaRevenge => "montezuma_revenge",
+            MrDo => "mr_do",
+            MsPacman => "ms_pacman",
+            NameThisGame => "name_this_game",
+            Othello => "othello",
+            Pacman => "pacman",
+            Phoenix => "phoenix",
+            Pitfall => "pitfall",
+            Pitfall2 => "pitfall2",
+            Pong => "pong",
+            Pooyan => "pooyan",
+            PrivateEye => "private_eye",
+            Qbert => "qbert",
+            Riverraid => "riverraid",
+            RoadRunner => "road_runner",
+            Robotank => "robotank",
+            Seaquest => "seaquest",
+            SirLancelot => "sir_lancelot",
+            Skiing => "skiing",
+            Solaris => "solaris",
+            SpaceInvaders => "space_invaders",
+            SpaceWar => "space_war",
+            StarGunner => "star_gunner",
+            Superman => "superman",
+            Surround => "surround",
+            Tennis => "tennis",
+            Tetris => "tetris",
+            TicTacToe3d => "tic_tac_toe_3d",
+            TimePilot => "time_pilot",
+            Trondead => "trondead",
+            Turmoil => "turmoil",
+            Tutankham => "tutankham",
+            UpNDown => "up_n_down",
+            Venture => "venture",
+            VideoCheckers => "video_checkers",
+            VideoChess => "video_chess",
+            VideoCube => "video_cube",
+            VideoPinball => "video_pinball",
+            Warlords => "warlords",
+            WizardOfWor => "wizard_of_wor",
+            WordZapper => "word_zapper",
+            YarsRevenge => "yars_revenge",
+            Zaxxon => "zaxxon",
+        }
+    }
+
+    /// Returns the filename that the ROM should be named, in order for the ALE to pick up on it and
+    /// use the correct settings.
+    pub fn filename(&self) -> &'static str {
+        use BundledRom::*;
+        match self {
+            Adventure => "adventure.bin",
+            AirRaid => "air_raid.bin",
+            Alien => "alien.bin",
+            Amidar => "amidar.bin",
+            Assault => "assault.bin",
+            Asterix => "asterix.bin",
+            Asteroids => "asteroids.bin",
+            Atlantis => "atlantis.bin",
+            Atlantis2 => "atlantis2.bin",
+            Backgammon => "backgammon.bin",
+            BankHeist => "bank_heist.bin",
+            BasicMath => "basic_math.bin",
+            BattleZone => "battle_zone.bin",
+            BeamRider => "beam_rider.bin",
+            Berzerk => "berzerk.bin",
+            Blackjack => "blackjack.bin",
+            Bowling => "bowling.bin",
+            Boxing => "boxing.bin",
+            Breakout => "breakout.bin",
+            Carnival => "carnival.bin",
+            Casino => "casino.bin",
+            Centipede => "centipede.bin",
+            ChopperCommand => "chopper_command.bin",
+            Combat => "combat.bin",
+            CrazyClimber => "crazy_climber.bin",
+            Crossbow => "crossbow.bin",
+            Darkchambers => "darkchambers.bin",
+            Defender => "defender.bin",
+            DemonAttack => "demon_attack.bin",
+            DonkeyKong => "donkey_kong.bin",
+            DoubleDunk => "double_dunk.bin",
+            Earthworld => "earthworld.bin",
+            ElevatorAction => "elevator_action.bin",
+            Enduro => "enduro.bin",
+            Entombed => "entombed.bin",
+            Et => "et.bin",
+            FishingDerby => "fishing_derby.bin",
+            FlagCapture => "flag_capture.bin",
+            Freeway => "freeway.bin",
+            Frogger => "frogger.bin",
+            Frostbite => "frostbite.bin",
+            Galaxian => "galaxian.bin",
+            Gopher => "gopher.bin",
+            Gravitar => "gravitar.bin",
+            Hangman => "hangman.bin",
+            HauntedHouse => "haunted_house.bin",
+            Hero => "hero.bin",
+            HumanCannonball => "human_cannonball.bin",
+            IceHockey => "ice_hockey.bin",
+            Jamesbond => "jamesbond.bin",
+            JourneyEscape => "journey_escape.bin",
+            Joust => "joust.bin",
+            Kaboom => "kaboom.bin",
+            Kangaroo => "kangaroo.bin",
+            KeystoneKapers => "keystone_kapers.bin",
+            KingKong => "king_kong.bin",
+            Klax => "klax.bin",
+            Koolaid => "koolaid.bin",
+            Krull => "krull.bin",
+            KungFuMaster => "kung_fu_master.bin",
+            LaserGates => "laser_gates.bin",
+            LostLuggage => "lost_luggage.bin",
+            MarioBros => "mario_bros.bin",
+            MazeCraze => "maze_craze.bin",
+            MiniatureGolf => "miniature_golf.bin",
+            MontezumaRevenge => "montezuma_revenge.bin",
+            MrDo => "mr_do.bin",
+            MsPacman => "ms_pacman.bin",
+            NameThisGame => "name_this_game.bin",
+            Othello => "othello.bin",
+            Pacman => "pacman.bin",
+            Phoenix => "phoenix.bin",
+            Pitfall => "pitfall.bin",
+            Pitfall2 => "pitfall2.bin",
+            Pong => "pong.bin",
+            Pooyan => "pooyan.bin",
+            PrivateEye => "private_eye.bin",
+            Qbert => "qbert.bin",
+            Riverraid => "riverraid.bin",
+            RoadRunner => "road_runner.bin",
+            Robotank => "robotank.bin",
+            Seaquest => "seaquest.bin",
+            SirLancelot => "sir_lancelot.bin",
+            Skiing => "skiing.bin",
+            Solaris => "solaris.bin",
+            SpaceInvaders => "space_invaders.bin",
+            SpaceWar => "space_war.bin",
+            StarGunner => "star_gunner.bin",
+            Superman => "superman.bin",
+            Surround => "surround.bin",
+            Tennis => "tennis.bin",
+            Tetris => "tetris.bin",
+            TicTacToe3d => "tic_tac_toe_3d.bin",
+            TimePilot => "time_pilot.bin",
+            Trondead => "trondead.bin",
+            Turmoil => "turmoil.bin",
+            Tutankham => "tutankham.bin",
+            UpNDown => "up_n_down.bin",
+            Venture => "venture.bin",
+            VideoCheckers => "video_checkers.bin",
+            VideoChess => "video_chess.bin",
+            VideoCube => "video_cube.bin",
+            VideoPinball => "video_pinball.bin",
+            Warlords => "warlords.bin",
+            WizardOfWor => "wizard_of_wor.bin",
+            WordZapper => "word_zapper.bin",
+            YarsRevenge => "yars_revenge.bin",
+            Zaxxon => "zaxxon.bin",
+        }
+    }
+}
+
+// lower case without separators, so that "MontezumaRevenge" matches "montezuma_revenge"
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+// strip Gymnasium decorations: "ALE/Breakout-v5", "BreakoutNoFrameskip-v4", "Breakout-ram-v5",
+// "Breakout-ramNoFrameskip-v4"; the frameskip suffix comes after -ram
+fn strip_gym_id(name: &str) -> &str {
+    let mut name = name.strip_prefix("ALE/").unwrap_or(name);
+    if let Some((base, version)) = name.rsplit_once("-v") {
+        if !version.is_empty() && version.chars().all(|c| c.is_ascii_digit()) {
+            name = base;
+        }
+    }
+    for suffix in ["NoFrameskip", "Deterministic"] {
+        name = name.strip_suffix(suffix).unwrap_or(name);
+    }
+    name.strip_suffix("-ram").unwrap_or(name)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// Parses snake case names (`"space_invaders"`) as well as Gymnasium ids
+/// (`"SpaceInvaders-v5"`, `"ALE/SpaceInvaders-v5"`, `"SpaceInvadersNoFrameskip-v4"`).
+impl FromStr for BundledRom {
+    type Err = ParseRomError;
+
+    fn from_str(name: &str) -> Result<BundledRom, ParseRomError> {
+        let key = normalize(strip_gym_id(name));
+        if let Some(rom) = BundledRom::iter().find(|rom| normalize(rom.name()) == key) {
+            return Ok(rom);
+        }
+        let suggestion = BundledRom::iter()
+            .map(|rom| (edit_distance(&key, &normalize(rom.name())), rom))
+            .min_by_key(|(distance, _)| *distance)
+            .filter(|(distance, _)| *distance <= 2.max(key.len() / 3))
+            .map(|(_, rom)| rom)
+            .or_else(|| {
+                // abbreviations such as "montezuma"
+                BundledRom::iter()
+                    .find(|rom| key.len() >= 3 && normalize(rom.name()).starts_with(&key))
+            });
+        Err(ParseRomError {
+            name: name.to_string(),
+            suggestion,
+        })
+    }
+}
+
+impl fmt::Display for BundledRom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_parse_in_any_case_and_spacing() {
+        for name in [
+            "space_invaders",
+            "SpaceInvaders",
+            "space-invaders",
+            "SPACE INVADERS",
+        ] {
+            assert_eq!(name.parse(), Ok(BundledRom::SpaceInvaders), "{}", name);
+        }
+        for rom in BundledRom::iter() {
+            assert_eq!(rom.name().parse(), Ok(rom));
+        }
+    }
+
+    #[test]
+    fn gym_ids_parse_to_their_game() {
+        for id in [
+            "Breakout-v0",
+            "Breakout-v4",
+            "ALE/Breakout-v5",
+            "BreakoutNoFrameskip-v4",
+            "BreakoutDeterministic-v4",
+            "Breakout-ram-v0",
+            "ALE/Breakout-ram-v5",
+            "Breakout-ramNoFrameskip-v4",
+            "Breakout-ramDeterministic-v4",
+        ] {
+            assert_eq!(id.parse(), Ok(BundledRom::Breakout), "{}", id);
+        }
+        assert_eq!(
+            "MontezumaRevengeNoFrameskip-v4".parse(),
+            Ok(BundledRom::MontezumaRevenge)
+        );
+        // only version suffixes are stripped
+        assert!("Breakout-vx".parse::<BundledRom>().is_err());
+    }
+
+    #[test]
+    fn unknown_names_suggest_a_close_game() {
+        let error = "breakuot".parse::<BundledRom>().unwrap_err();
+        assert_eq!(error.suggestion, Some(BundledRom::Breakout));
+        assert_eq!(
+            error.to_string(),
+            "ROM breakuot not supported, did you mean breakout?"
+        );
+        // abbreviations
+        let error = "montezuma".parse::<BundledRom>().unwrap_err();
+        assert_eq!(error.suggestion, Some(BundledRom::MontezumaRevenge));
+
+        let error = "minecraft".parse::<BundledRom>().unwrap_err();
+        assert_eq!(error.suggestion, None);
+        assert_eq!(error.to_string(), "ROM minecraft not supported");
+    }
+}
@@ -0,0 +1,23 @@
+//! Seed derivation for vectorized environments.
+//!
+//! Sub-environment `i` of a `VecAtari` created (or reset) with base seed `s` is seeded with
+//! `derive_seed(s, i)`. The base seed and the index are mixed through SplitMix64, so that
+//! neighbouring environments, as well as runs started from adjacent base seeds, receive
+//! unrelated ALE seeds instead of overlapping `s + i` ranges.
+
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(GOLDEN_GAMMA);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Returns the ALE seed of sub-environment `index` for the base seed `seed`.
+///
+/// The result lies in `[0, 2^31)`, the range accepted by ALE's `random_seed` setting.
+pub fn derive_seed(seed: i32, index: usize) -> i32 {
+    let hashed = splitmix64(splitmix64(seed as u32 as u64) ^ index as u64);
+    (hashed >> 33) as i32
+}
@@ -0,0 +1,189 @@
+//! Named benchmark suites, so that experiments refer to a stable identifier such as `"atari-5"`
+//! instead of a hand-copied list of games.
+
+use crate::rom::BundledRom;
+use crate::rom::BundledRom::*;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Suite {
+    /// Five games whose scores best predict the Atari-57 median (Aitchison et al., 2023).
+    Atari5,
+    /// Ten-game extension of Atari-5 from the same work.
+    Atari10,
+    /// The 26 games of the Atari 100k benchmark (Kaiser et al., 2020).
+    Atari100k,
+    /// The 57 games with human reference scores used since DQN.
+    Atari57,
+    /// The five games Machado et al. (2018) recommend for tuning hyperparameters, taken from
+    /// the training set of Bellemare et al. (2013).
+    MachadoTraining,
+}
+
+const ATARI_5: [BundledRom; 5] = [BattleZone, DoubleDunk, NameThisGame, Phoenix, Qbert];
+
+const ATARI_10: [BundledRom; 10] = [
+    Amidar,
+    BattleZone,
+    Bowling,
+    DoubleDunk,
+    Frostbite,
+    KungFuMaster,
+    NameThisGame,
+    Phoenix,
+    Qbert,
+    Riverraid,
+];
+
+const ATARI_100K: [BundledRom; 26] = [
+    Alien,
+    Amidar,
+    Assault,
+    Asterix,
+    BankHeist,
+    BattleZone,
+    Boxing,
+    Breakout,
+    ChopperCommand,
+    CrazyClimber,
+    DemonAttack,
+    Freeway,
+    Frostbite,
+    Gopher,
+    Hero,
+    Jamesbond,
+    Kangaroo,
+    Krull,
+    KungFuMaster,
+    MsPacman,
+    Pong,
+    PrivateEye,
+    Qbert,
+    RoadRunner,
+    Seaquest,
+    UpNDown,
+];
+
+const ATARI_57: [BundledRom; 57] = [
+    Alien,
+    Amidar,
+    Assault,
+    Asterix,
+    Asteroids,
+    Atlantis,
+    BankHeist,
+    BattleZone,
+    BeamRider,
+    Berzerk,
+    Bowling,
+    Boxing,
+    Breakout,
+    Centipede,
+    ChopperCommand,
+    CrazyClimber,
+    Defender,
+    DemonAttack,
+    DoubleDunk,
+    Enduro,
+    FishingDerby,
+    Freeway,
+    Frostbite,
+    Gopher,
+    Gravitar,
+    Hero,
+    IceHockey,
+    Jamesbond,
+    Kangaroo,
+    Krull,
+    KungFuMaster,
+    MontezumaRevenge,
+    MsPacman,
+    NameThisGame,
+    Phoenix,
+    Pitfall,
+    Pong,
+    PrivateEye,
+    Qbert,
+    Riverraid,
+    RoadRunner,
+    Robotank,
+    Seaquest,
+    Skiing,
+    Solaris,
+    SpaceInvaders,
+    StarGunner,
+    Surround,
+    Tennis,
+    TimePilot,
+    Tutankham,
+    UpNDown,
+    Venture,
+    VideoPinball,
+    WizardOfWor,
+    YarsRevenge,
+    Zaxxon,
+];
+
+const MACHADO_TRAINING: [BundledRom; 5] = [Asterix, BeamRider, Freeway, Seaquest, SpaceInvaders];
+
+impl Suite {
+    pub const ALL: [Suite; 5] = [
+        Suite::Atari5,
+        Suite::Atari10,
+        Suite::Atari100k,
+        Suite::Atari57,
+        Suite::MachadoTraining,
+    ];
+
+    /// Stable identifier of the suite, e.g. `"atari-100k"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Suite::Atari5 => "atari-5",
+            Suite::Atari10 => "atari-10",
+            Suite::Atari100k => "atari-100k",
+            Suite::Atari57 => "atari-57",
+            Suite::MachadoTraining => "machado-training",
+        }
+    }
+
+    /// Games of the suite, in alphabetical order.
+    pub fn games(&self) -> &'static [BundledRom] {
+        match self {
+            Suite::Atari5 => &ATARI_5,
+            Suite::Atari10 => &ATARI_10,
+            Suite::Atari100k => &ATARI_100K,
+            Suite::Atari57 => &ATARI_57,
+            Suite::MachadoTraining => &MACHADO_TRAINING,
+        }
+    }
+}
+
+/// Parses suite names case insensitively; `"atari-26"` is accepted for Atari 100k.
+impl FromStr for Suite {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Suite, String> {
+        let name = name.to_ascii_lowercase().replace('_', "-");
+        if name == "atari-26" {
+            return Ok(Suite::Atari100k);
+        }
+        Suite::ALL
+            .into_iter()
+            .find(|suite| suite.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Suite::ALL.iter().map(|suite| suite.name()).collect();
+                format!(
+                    "Unknown suite {}, expected one of {}",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for Suite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
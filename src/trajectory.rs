@@ -0,0 +1,611 @@
+//! Per-episode trajectory files written by `Atari::start_recording`.
+//!
+//! An episode of `T` steps is stored as `T + 1` rows, row `t` holding the observation at
+//! timestep `t`, the lives left then, the action taken from it and the reward that followed.
+//! The last row holds the final observation with a `Noop` action and no reward, and tells
+//! whether the episode terminated or was truncated. A file is laid out as follows, all
+//! integers little endian:
+//!
+//! | size                | content                                                         |
+//! |---------------------|-----------------------------------------------------------------|
+//! | 8                   | magic `b"ALETRAJ1"`                                             |
+//! | 4                   | length of the header (`u32`)                                    |
+//! | header length       | UTF-8 `key=value` lines: game, ROM, seed, shape and settings    |
+//! | then for each chunk |                                                                 |
+//! | 4                   | rows in the chunk (`u32`)                                       |
+//! | 4                   | length of the compressed chunk (`u32`)                          |
+//! | compressed length   | zlib stream of the columns of the rows, one after the other     |
+//!
+//! The columns of a chunk are the observations (`height × width × channels` bytes each), the
+//! actions (`u8`), the rewards (`i32`), the lives (`i32`) and flags (`u8`, bit 0 terminal,
+//! bit 1 truncation). Episodes are written to `<name>.partial` and renamed to `<name>` once
+//! complete, so a crash never leaves a truncated file behind under the final name.
+
+use crate::action::Action;
+use crate::rom::BundledRom;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"ALETRAJ1";
+const TERMINAL: u8 = 1;
+const TRUNCATION: u8 = 2;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordingOptions {
+    /// Rows compressed together; larger chunks compress better but buffer more frames.
+    pub chunk_steps: usize,
+    /// zlib level from 0 (store) to 9 (smallest).
+    pub compression: u32,
+}
+
+impl Default for RecordingOptions {
+    fn default() -> Self {
+        RecordingOptions {
+            chunk_steps: 1000,
+            compression: 6,
+        }
+    }
+}
+
+/// How the emulator was set up when an episode started, which decides whether the episode
+/// can be reproduced from its seed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EpisodeStart {
+    /// First episode of an env created with the seed.
+    Created,
+    /// Episode started by a reset with the seed.
+    Reseeded,
+    /// Episode started by a reset without a seed, continuing the emulator's random state.
+    Continued,
+}
+
+impl EpisodeStart {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EpisodeStart::Created => "created",
+            EpisodeStart::Reseeded => "reseeded",
+            EpisodeStart::Continued => "continued",
+        }
+    }
+}
+
+/// An episode read back from a trajectory file, with one entry per row in each field.
+#[derive(Clone, Debug)]
+pub struct Trajectory {
+    pub game: BundledRom,
+    pub seed: i32,
+    pub start: EpisodeStart,
+    /// Index of the env in its `VecAtari`, if it was recorded from one.
+    pub env: Option<usize>,
+    pub screen_size: (usize, usize),
+    pub channels: usize,
+    /// Remaining header entries, e.g. `("repeat_action_probability", "0.25")`.
+    pub settings: Vec<(String, String)>,
+    pub obs: Vec<u8>,
+    pub actions: Vec<Action>,
+    pub rewards: Vec<i32>,
+    pub lives: Vec<i32>,
+    pub terminals: Vec<bool>,
+    pub truncations: Vec<bool>,
+}
+
+impl Trajectory {
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Trajectory> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut file = BufReader::new(file);
+        let mut magic = [0u8; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("Not a trajectory file".to_string()));
+        }
+        let len = read_u32(&mut file)?;
+        let header = read_bytes(&mut file, len)?;
+        let header = String::from_utf8(header)
+            .map_err(|_| invalid("Trajectory header is not UTF-8".to_string()))?;
+        let mut trajectory = Trajectory::from_header(&header)?;
+
+        let obs_size = trajectory.obs_size();
+        loop {
+            let mut rows = [0u8; 4];
+            match file.read_exact(&mut rows) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+            let rows = u32::from_le_bytes(rows) as usize;
+            let len = read_u32(&mut file)?;
+            let compressed = read_bytes(&mut file, len)?;
+            let size = obs_size
+                .checked_add(10)
+                .and_then(|row| rows.checked_mul(row))
+                .ok_or_else(|| invalid("Trajectory chunk size overflows".to_string()))?;
+            // sizes come from the file, so neither trust them for the allocation nor
+            // decompress past them
+            let mut columns = Vec::with_capacity(size.min(file_len as usize));
+            ZlibDecoder::new(&compressed[..])
+                .take(size as u64 + 1)
+                .read_to_end(&mut columns)?;
+            if columns.len() != size {
+                return Err(invalid("Trajectory chunk has the wrong size".to_string()));
+            }
+
+            let (obs, rest) = columns.split_at(rows * obs_size);
+            let (actions, rest) = rest.split_at(rows);
+            let (rewards, rest) = rest.split_at(4 * rows);
+            let (lives, flags) = rest.split_at(4 * rows);
+            trajectory.obs.extend_from_slice(obs);
+            for &action in actions {
+                let action = Action::try_from(action as i32).map_err(invalid)?;
+                trajectory.actions.push(action);
+            }
+            trajectory
+                .rewards
+                .extend(rewards.chunks_exact(4).map(le_i32));
+            trajectory.lives.extend(lives.chunks_exact(4).map(le_i32));
+            for &flag in flags {
+                trajectory.terminals.push(flag & TERMINAL != 0);
+                trajectory.truncations.push(flag & TRUNCATION != 0);
+            }
+        }
+        Ok(trajectory)
+    }
+
+    /// Number of rows, one more than the number of steps.
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Bytes of one observation.
+    pub fn obs_size(&self) -> usize {
+        self.screen_size.0 * self.screen_size.1 * self.channels
+    }
+
+    /// Observation of row `t`.
+    pub fn obs_at(&self, t: usize) -> &[u8] {
+        &self.obs[t * self.obs_size()..(t + 1) * self.obs_size()]
+    }
+
+    /// Sum of the rewards of the episode.
+    pub fn score(&self) -> i32 {
+        self.rewards.iter().sum()
+    }
+
+    /// Value of a header entry not parsed into a field.
+    pub fn setting(&self, key: &str) -> Option<&str> {
+        self.settings
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn from_header(header: &str) -> io::Result<Trajectory> {
+        let mut trajectory = Trajectory {
+            game: BundledRom::Pong,
+            seed: 0,
+            start: EpisodeStart::Continued,
+            env: None,
+            screen_size: (0, 0),
+            channels: 0,
+            settings: Vec::new(),
+            obs: Vec::new(),
+            actions: Vec::new(),
+            rewards: Vec::new(),
+            lives: Vec::new(),
+            terminals: Vec::new(),
+            truncations: Vec::new(),
+        };
+        let mut required = ["game", "seed", "start", "height", "width", "channels"].to_vec();
+        for line in header.lines() {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("Invalid trajectory header line {:?}", line)))?;
+            let bad_value = || invalid(format!("Invalid value {:?} for {}", value, key));
+            let number = || value.parse::<usize>().map_err(|_| bad_value());
+            required.retain(|k| *k != key);
+            match key {
+                "game" => trajectory.game = value.parse().map_err(|_| bad_value())?,
+                "seed" => trajectory.seed = value.parse().map_err(|_| bad_value())?,
+                "start" => {
+                    trajectory.start = match value {
+                        "created" => EpisodeStart::Created,
+                        "reseeded" => EpisodeStart::Reseeded,
+                        "continued" => EpisodeStart::Continued,
+                        _ => return Err(bad_value()),
+                    }
+                }
+                "env" => trajectory.env = Some(number()?),
+                "height" => trajectory.screen_size.0 = number()?,
+                "width" => trajectory.screen_size.1 = number()?,
+                "channels" => trajectory.channels = number()?,
+                _ => trajectory
+                    .settings
+                    .push((key.to_string(), value.to_string())),
+            }
+        }
+        if let Some(key) = required.first() {
+            return Err(invalid(format!("Trajectory header has no {}", key)));
+        }
+        let (height, width) = trajectory.screen_size;
+        if height
+            .checked_mul(width)
+            .and_then(|size| size.checked_mul(trajectory.channels))
+            .is_none()
+        {
+            return Err(invalid("Trajectory observation size overflows".to_string()));
+        }
+        Ok(trajectory)
+    }
+}
+
+// writes the episodes of one env to a directory, one file each; the first IO error stops
+// recording and is kept until the recorder is finished
+pub(crate) struct Recorder {
+    dir: PathBuf,
+    env: Option<usize>,
+    options: RecordingOptions,
+    episodes: usize,
+    episode: Option<EpisodeWriter>,
+    error: Option<io::Error>,
+}
+
+impl Recorder {
+    pub(crate) fn new(dir: &Path, env: Option<usize>, options: RecordingOptions) -> Recorder {
+        Recorder {
+            dir: dir.to_path_buf(),
+            episodes: next_episode(dir, &prefix(env)),
+            env,
+            options,
+            episode: None,
+            error: None,
+        }
+    }
+
+    // start an episode from its first observation, ending any open one as truncated
+    pub(crate) fn begin(&mut self, header: &[(String, String)], obs: &[u8], lives: i32) {
+        self.close(TRUNCATION);
+        let mut header = header.to_vec();
+        if let Some(env) = self.env {
+            header.push(("env".to_string(), env.to_string()));
+        }
+        let name = format!("{}{:06}.traj", prefix(self.env), self.episodes);
+        self.episodes += 1;
+        let episode = EpisodeWriter::create(self.dir.join(name), &header, &self.options);
+        self.episode = self.check(episode);
+        if let Some(episode) = self.episode.as_mut() {
+            episode.pending = Some((obs.to_vec(), lives));
+        }
+    }
+
+    // whether an episode is open and wants the observation after the next step
+    pub(crate) fn is_recording(&self) -> bool {
+        self.episode.is_some()
+    }
+
+    pub(crate) fn step(
+        &mut self,
+        action: Action,
+        reward: i32,
+        obs: &[u8],
+        lives: i32,
+        terminal: bool,
+        truncation: bool,
+    ) {
+        let Some(episode) = self.episode.as_mut() else {
+            return;
+        };
+        let written = episode
+            .push(action, reward, 0)
+            .map(|()| episode.pending = Some((obs.to_vec(), lives)));
+        if self.check(written).is_some() && (terminal || truncation) {
+            let flags = match terminal {
+                true => TERMINAL,
+                false => TRUNCATION,
+            };
+            self.close(flags);
+        }
+    }
+
+    // write any open episode as truncated and return the first error met while recording
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.close(TRUNCATION);
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn close(&mut self, flags: u8) {
+        if let Some(episode) = self.episode.take() {
+            let closed = episode.close(flags);
+            self.check(closed);
+        }
+    }
+
+    fn check<T>(&mut self, result: io::Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                if let Some(episode) = self.episode.take() {
+                    let _ = fs::remove_file(&episode.partial);
+                }
+                self.error.get_or_insert(e);
+                None
+            }
+        }
+    }
+}
+
+struct EpisodeWriter {
+    file: BufWriter<File>,
+    path: PathBuf,
+    partial: PathBuf,
+    chunk_steps: usize,
+    compression: Compression,
+    // observation and lives of the row waiting for its action
+    pending: Option<(Vec<u8>, i32)>,
+    rows: usize,
+    obs: Vec<u8>,
+    actions: Vec<u8>,
+    rewards: Vec<u8>,
+    lives: Vec<u8>,
+    flags: Vec<u8>,
+}
+
+impl EpisodeWriter {
+    fn create(
+        path: PathBuf,
+        header: &[(String, String)],
+        options: &RecordingOptions,
+    ) -> io::Result<EpisodeWriter> {
+        let mut partial = path.clone().into_os_string();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        let mut file = BufWriter::new(File::create(&partial)?);
+        let header: String = header
+            .iter()
+            .map(|(key, value)| format!("{}={}\n", key, value))
+            .collect();
+        file.write_all(MAGIC)?;
+        file.write_all(&(header.len() as u32).to_le_bytes())?;
+        file.write_all(header.as_bytes())?;
+        Ok(EpisodeWriter {
+            file,
+            path,
+            partial,
+            chunk_steps: options.chunk_steps.max(1),
+            compression: Compression::new(options.compression.min(9)),
+            pending: None,
+            rows: 0,
+            obs: Vec::new(),
+            actions: Vec::new(),
+            rewards: Vec::new(),
+            lives: Vec::new(),
+            flags: Vec::new(),
+        })
+    }
+
+    // complete the pending row with the action taken from it
+    fn push(&mut self, action: Action, reward: i32, flags: u8) -> io::Result<()> {
+        let (obs, lives) = self
+            .pending
+            .take()
+            .expect("Trajectory row has no observation");
+        self.obs.extend_from_slice(&obs);
+        self.actions.push(action as u8);
+        self.rewards.extend_from_slice(&reward.to_le_bytes());
+        self.lives.extend_from_slice(&lives.to_le_bytes());
+        self.flags.push(flags);
+        self.rows += 1;
+        match self.rows >= self.chunk_steps {
+            true => self.flush_chunk(),
+            false => Ok(()),
+        }
+    }
+
+    fn flush_chunk(&mut self) -> io::Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let mut encoder = ZlibEncoder::new(Vec::new(), self.compression);
+        for column in [
+            &mut self.obs,
+            &mut self.actions,
+            &mut self.rewards,
+            &mut self.lives,
+            &mut self.flags,
+        ] {
+            encoder.write_all(column)?;
+            column.clear();
+        }
+        let compressed = encoder.finish()?;
+        self.file.write_all(&(self.rows as u32).to_le_bytes())?;
+        self.file
+            .write_all(&(compressed.len() as u32).to_le_bytes())?;
+        self.file.write_all(&compressed)?;
+        self.rows = 0;
+        Ok(())
+    }
+
+    // write the final row with the end of the episode and move the file to its name
+    fn close(mut self, flags: u8) -> io::Result<()> {
+        let written = self
+            .push(Action::Noop, 0, flags)
+            .and_then(|()| self.flush_chunk())
+            .and_then(|()| self.file.flush())
+            .and_then(|()| fs::rename(&self.partial, &self.path));
+        if written.is_err() {
+            let _ = fs::remove_file(&self.partial);
+        }
+        written
+    }
+}
+
+// file names are <prefix><episode>.traj
+fn prefix(env: Option<usize>) -> String {
+    match env {
+        Some(env) => format!("env-{:03}-episode-", env),
+        None => "episode-".to_string(),
+    }
+}
+
+// number after the last episode already in dir, so that recording again does not overwrite
+fn next_episode(dir: &Path, prefix: &str) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            let name = name.strip_suffix(".partial").unwrap_or(&name);
+            let episode = name.strip_prefix(prefix)?.strip_suffix(".traj")?;
+            episode.parse::<usize>().ok()
+        })
+        .map(|episode| episode + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+// len bytes, allocated as they are read rather than up front
+fn read_bytes(reader: &mut impl Read, len: u32) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    match bytes.len() == len as usize {
+        true => Ok(bytes),
+        false => Err(io::ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+fn le_i32(bytes: &[u8]) -> i32 {
+    i32::from_le_bytes(bytes.try_into().unwrap())
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Vec<(String, String)> {
+        [
+            ("game", "pong"),
+            ("seed", "7"),
+            ("start", "reseeded"),
+            ("height", "2"),
+            ("width", "3"),
+            ("channels", "1"),
+            ("frame_skip", "4"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+    }
+
+    // records an episode of 5 steps in chunks of 2 rows and returns its path
+    fn record(dir: &Path) -> PathBuf {
+        let options = RecordingOptions {
+            chunk_steps: 2,
+            compression: 6,
+        };
+        let mut recorder = Recorder::new(dir, Some(3), options);
+        recorder.begin(&header(), &[0; 6], 3);
+        for t in 1..=5u8 {
+            let lives = 3 - (t >= 4) as i32;
+            recorder.step(Action::Fire, t as i32 - 2, &[t; 6], lives, t == 5, false);
+        }
+        recorder.finish().unwrap();
+        dir.join("env-003-episode-000000.traj")
+    }
+
+    #[test]
+    fn write_read_round_trip() {
+        let dir = tempdir::TempDir::new("ale-env-traj").unwrap();
+        let trajectory = Trajectory::read(record(dir.path())).unwrap();
+        assert_eq!(trajectory.game, BundledRom::Pong);
+        assert_eq!(trajectory.seed, 7);
+        assert_eq!(trajectory.start, EpisodeStart::Reseeded);
+        assert_eq!(trajectory.env, Some(3));
+        assert_eq!((trajectory.screen_size, trajectory.channels), ((2, 3), 1));
+        assert_eq!(trajectory.setting("frame_skip"), Some("4"));
+        assert_eq!(trajectory.len(), 6);
+        for t in 0..6 {
+            assert_eq!(trajectory.obs_at(t), [t as u8; 6]);
+        }
+        let mut actions = vec![Action::Fire; 5];
+        actions.push(Action::Noop);
+        assert_eq!(trajectory.actions, actions);
+        assert_eq!(trajectory.rewards, [-1, 0, 1, 2, 3, 0]);
+        assert_eq!(trajectory.lives, [3, 3, 3, 3, 2, 2]);
+        assert_eq!(trajectory.score(), 5);
+        assert_eq!(
+            trajectory.terminals,
+            [false, false, false, false, false, true]
+        );
+        assert!(trajectory.truncations.iter().all(|&truncation| !truncation));
+    }
+
+    #[test]
+    fn corrupt_files_are_rejected() {
+        let dir = tempdir::TempDir::new("ale-env-traj").unwrap();
+        let path = record(dir.path());
+        let data = fs::read(&path).unwrap();
+        let header_len = u32::from_le_bytes(data[8..12].try_into().unwrap()) as usize;
+        let chunk = 12 + header_len;
+        let corrupt = |edit: &dyn Fn(&mut Vec<u8>)| {
+            let mut data = data.clone();
+            edit(&mut data);
+            fs::write(&path, data).unwrap();
+            Trajectory::read(&path).unwrap_err().kind()
+        };
+
+        // header and chunk lengths past the end of the file
+        let eof = io::ErrorKind::UnexpectedEof;
+        assert_eq!(
+            corrupt(&|data| data[8..12].copy_from_slice(&[0xff; 4])),
+            eof
+        );
+        assert_eq!(
+            corrupt(&|data| data[chunk + 4..chunk + 8].copy_from_slice(&[0xff; 4])),
+            eof
+        );
+        // row counts that do not match the chunk, small or huge
+        let invalid = io::ErrorKind::InvalidData;
+        assert_eq!(
+            corrupt(&|data| data[chunk..chunk + 4].copy_from_slice(&[3, 0, 0, 0])),
+            invalid
+        );
+        assert_eq!(
+            corrupt(&|data| data[chunk..chunk + 4].copy_from_slice(&[0xff; 4])),
+            invalid
+        );
+        // observation sizes that overflow
+        let huge = header()
+            .iter()
+            .map(|(key, value)| match key.as_str() {
+                "height" | "width" => format!("{}={}\n", key, usize::MAX),
+                _ => format!("{}={}\n", key, value),
+            })
+            .collect::<String>();
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&(huge.len() as u32).to_le_bytes());
+        data.extend_from_slice(huge.as_bytes());
+        fs::write(&path, data).unwrap();
+        assert_eq!(Trajectory::read(&path).unwrap_err().kind(), invalid);
+    }
+}
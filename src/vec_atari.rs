@@ -1,111 +1,886 @@
+use crate::action::Action;
 pub use crate::atari::Atari;
-use pyo3::prelude::*;
-use rand;
-use rand::Rng;
-use std::sync::mpsc;
-use std::sync::{Arc, Mutex};
-use threadpool::ThreadPool;
-
-#[pyclass]
+use crate::config::AleConfig;
+#[cfg(unix)]
+use crate::obs_ring::ObsRing;
+use crate::rom::BundledRom;
+use crate::seeding::derive_seed;
+use crate::trajectory::RecordingOptions;
+use std::any::Any;
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Barrier};
+use std::thread::{self, JoinHandle};
+
+// observation rings live in POSIX shared memory, elsewhere none can be attached
+#[cfg(not(unix))]
+enum ObsRing {}
+
+#[cfg(not(unix))]
+impl ObsRing {
+    fn push(&self, _env: usize, _write: impl FnOnce(&mut [u8])) {
+        match *self {}
+    }
+
+    fn head(&self, _env: usize) -> u64 {
+        match *self {}
+    }
+
+    fn read(&self, _env: usize, _t: u64, _out: &mut [u8]) -> bool {
+        match *self {}
+    }
+}
+
+/// Outcome of one env in a `VecAtari::step` or `VecAtari::reset` call.
+#[derive(Clone, Debug)]
+pub struct StepResult {
+    pub index: usize,
+    pub game: BundledRom,
+    pub obs: Vec<u8>,
+    pub reward: i32,
+    pub terminal: bool,
+    pub truncation: bool,
+    pub life_loss: bool,
+    /// Score of the episode that ended in this step; the env has already been reset.
+    pub score: Option<i32>,
+    /// Last observation of the episode that ended in this step, taken before the reset.
+    pub final_obs: Option<Vec<u8>>,
+}
+
+/// Game, mode and difficulty run by one env of a `VecAtari`; a given mode or difficulty
+/// overrides the one of the shared `AleConfig`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EnvSpec {
+    pub game: BundledRom,
+    pub mode: Option<i32>,
+    pub difficulty: Option<i32>,
+}
+
+impl From<BundledRom> for EnvSpec {
+    fn from(game: BundledRom) -> EnvSpec {
+        EnvSpec {
+            game,
+            mode: None,
+            difficulty: None,
+        }
+    }
+}
+
+/// Threads stepping the envs of a `VecAtari`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorkerConfig {
+    /// Number of worker threads, each owning a contiguous slice of envs; defaults to the
+    /// available parallelism, and never exceeds the number of envs.
+    pub num_workers: Option<usize>,
+    /// CPUs to pin the workers to, worker `w` runs on `cpus[w % cpus.len()]`; Linux only.
+    pub cpus: Option<Vec<usize>>,
+    /// Replace an env that panics with a fresh one running the same seed, instead of failing
+    /// every later call. The step that panicked then returns the first observation of the
+    /// fresh env with `truncation` set.
+    pub recreate_failed_envs: bool,
+}
+
+/// Panic of an env while it was stepped or reset, caught in its worker thread.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvError {
+    pub index: usize,
+    pub game: BundledRom,
+    pub message: String,
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "env {} ({}) panicked: {}",
+            self.index, self.game, self.message
+        )
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+#[derive(Clone, Copy)]
+enum Command {
+    Step,
+    Reset(Option<i32>),
+    // start or stop recording as given by Shared::recording
+    Record,
+    Close,
+}
+
+// Buffers shared by the caller and the workers, handed over with two barriers: the caller
+// writes the command and actions before `start`, each worker then writes the results of its
+// own envs only, and the caller reads them after `done`. The barriers order these accesses,
+// so no locks are needed.
+struct Shared {
+    command: UnsafeCell<Command>,
+    actions: UnsafeCell<Vec<Action>>,
+    results: Vec<UnsafeCell<StepResult>>,
+    failures: Vec<UnsafeCell<Option<Failure>>>,
+    ring: UnsafeCell<Option<Arc<ObsRing>>>,
+    recording: UnsafeCell<Option<(PathBuf, RecordingOptions)>>,
+    recording_errors: Vec<UnsafeCell<Option<io::Error>>>,
+    start: Barrier,
+    done: Barrier,
+}
+
+unsafe impl Sync for Shared {}
+
+// panic of an env in the last command, recovered when the env was re-created
+struct Failure {
+    message: String,
+    recovered: bool,
+}
+
+// creates the env of one spec with a given seed, or returns why it cannot be created
+pub(crate) type MakeEnv = Box<dyn Fn(i32) -> Result<Atari, String> + Send + Sync>;
+
+// an env owned by a worker, with its index in the batch and what is needed to re-create it
+struct Slot {
+    index: usize,
+    env: Atari,
+    make_env: MakeEnv,
+    seed: i32,
+    // set once the env panicked without being re-created
+    failure: Option<String>,
+}
+
 pub struct VecAtari {
-    envs: Vec<Arc<Mutex<Atari>>>,
-    pool: ThreadPool,
-    action_space: Vec<i32>,
-    sender: mpsc::Sender<(usize, Vec<u8>, i32, bool, bool, bool, Option<i32>)>,
-    receiver: Arc<Mutex<mpsc::Receiver<(usize, Vec<u8>, i32, bool, bool, bool, Option<i32>)>>>,
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+    // envs of each worker
+    worker_envs: Vec<Range<usize>>,
+    games: Vec<BundledRom>,
+    seeds: Vec<i32>,
+    action_space: Vec<Action>,
+    screen_size: (usize, usize),
+    // only checked against attached observation rings
+    #[cfg_attr(not(unix), allow(dead_code))]
+    channels: usize,
+    recovered_errors: Vec<EnvError>,
 }
 
-#[pymethods]
 impl VecAtari {
-    #[new]
-    pub fn new(num_envs: usize, game: &str, max_frames: u32, gray_scale: bool, seed: i32) -> Self {
-        let pool = ThreadPool::new(num_envs);
-        let envs: Vec<Arc<Mutex<Atari>>> = (0..num_envs)
-            .map(|i| {
-                Arc::new(Mutex::new(Atari::new(
-                    game,
-                    max_frames,
-                    gray_scale,
-                    Some(seed + i as i32),
-                )))
+    pub fn new(
+        num_envs: usize,
+        game: &str,
+        max_frames: u32,
+        gray_scale: bool,
+        seed: i32,
+        max_episode_steps: Option<u32>,
+        config: Option<AleConfig>,
+    ) -> Self {
+        let spec = EnvSpec::from(BundledRom::name2rom(game));
+        VecAtari::with_specs(
+            vec![spec; num_envs],
+            max_frames,
+            gray_scale,
+            seed,
+            max_episode_steps,
+            config,
+            None,
+        )
+    }
+
+    // one env per spec, e.g. for multi-task training; games with different minimal action
+    // sets need config.full_action_space, and smaller screens are zero padded at the bottom
+    // and right up to the largest one; panics where try_with_specs returns an error
+    pub fn with_specs(
+        specs: Vec<EnvSpec>,
+        max_frames: u32,
+        gray_scale: bool,
+        seed: i32,
+        max_episode_steps: Option<u32>,
+        config: Option<AleConfig>,
+        workers: Option<WorkerConfig>,
+    ) -> Self {
+        VecAtari::try_with_specs(
+            specs,
+            max_frames,
+            gray_scale,
+            seed,
+            max_episode_steps,
+            config,
+            workers,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // with_specs returning an error when there are no specs, an env cannot be created, the
+    // action sets of the games differ, the worker config is invalid or the workers cannot be
+    // pinned to their CPUs
+    pub fn try_with_specs(
+        specs: Vec<EnvSpec>,
+        max_frames: u32,
+        gray_scale: bool,
+        seed: i32,
+        max_episode_steps: Option<u32>,
+        config: Option<AleConfig>,
+        workers: Option<WorkerConfig>,
+    ) -> Result<Self, String> {
+        if specs.is_empty() {
+            return Err("VecAtari needs at least one env".to_string());
+        }
+        let workers = workers.unwrap_or_default();
+        let num_workers = worker_count(&workers, specs.len())?;
+        let make_envs = env_factories(
+            &specs,
+            max_frames,
+            gray_scale,
+            max_episode_steps,
+            &config.unwrap_or_default(),
+        );
+        let envs: Vec<Atari> = make_envs
+            .iter()
+            .enumerate()
+            .map(|(i, make_env)| make_env(derive_seed(seed, i)))
+            .collect::<Result<_, _>>()?;
+
+        let games: Vec<BundledRom> = specs.iter().map(|spec| spec.game).collect();
+        let seeds: Vec<i32> = envs.iter().map(|env| env.get_seed()).collect();
+        let (action_space, screen_size) = shared_spaces(&envs)?;
+        let channels = match gray_scale {
+            true => 1,
+            false => 3,
+        };
+
+        let num_envs = envs.len();
+        let shared = Arc::new(Shared {
+            command: UnsafeCell::new(Command::Step),
+            actions: UnsafeCell::new(vec![Action::Noop; num_envs]),
+            results: games
+                .iter()
+                .enumerate()
+                .map(|(index, &game)| {
+                    UnsafeCell::new(StepResult {
+                        index,
+                        game,
+                        obs: vec![0u8; screen_size.0 * screen_size.1 * channels],
+                        reward: 0,
+                        terminal: false,
+                        truncation: false,
+                        life_loss: false,
+                        score: None,
+                        final_obs: None,
+                    })
+                })
+                .collect(),
+            failures: (0..num_envs).map(|_| UnsafeCell::new(None)).collect(),
+            ring: UnsafeCell::new(None),
+            recording: UnsafeCell::new(None),
+            recording_errors: (0..num_envs).map(|_| UnsafeCell::new(None)).collect(),
+            start: Barrier::new(num_workers + 1),
+            done: Barrier::new(num_workers + 1),
+        });
+
+        // split the envs into contiguous slices, one per worker
+        let mut slots: Vec<Slot> = envs
+            .into_iter()
+            .zip(make_envs)
+            .enumerate()
+            .map(|(index, (env, make_env))| Slot {
+                index,
+                seed: env.get_seed(),
+                env,
+                make_env,
+                failure: None,
             })
             .collect();
-        let action_space = envs[0].lock().unwrap().get_action_set();
-        let (sender, receiver) = mpsc::channel();
-        let receiver = Arc::new(Mutex::new(receiver));
-        Self {
-            envs,
-            pool,
+        let recreate = workers.recreate_failed_envs;
+        let mut handles = Vec::with_capacity(num_workers);
+        let (pinned_tx, pinned) = mpsc::channel();
+        let envs_of_workers = worker_envs(num_envs, num_workers);
+        for (w, envs) in envs_of_workers.iter().enumerate() {
+            let shared = shared.clone();
+            let owned: Vec<Slot> = slots.drain(..envs.len()).collect();
+            let cpu = workers.cpus.as_ref().map(|cpus| cpus[w % cpus.len()]);
+            let pinned_tx = pinned_tx.clone();
+            let handle = thread::Builder::new()
+                .name(format!("vec-atari-{}", w))
+                .spawn(move || {
+                    // a worker that failed to pin itself still waits for the close command
+                    let _ = pinned_tx.send(cpu.map_or(Ok(()), pin_current_thread));
+                    run_worker(shared, owned, screen_size, channels, recreate)
+                })
+                .expect("Spawn VecAtari worker failed");
+            handles.push(handle);
+        }
+
+        let vec_env = Self {
+            shared,
+            workers: handles,
+            worker_envs: envs_of_workers,
+            games,
+            seeds,
             action_space,
-            sender,
-            receiver,
+            screen_size,
+            channels,
+            recovered_errors: Vec::new(),
+        };
+        // on an error, dropping vec_env closes the workers
+        let mut errors = pinned.iter().take(num_workers).filter_map(Result::err);
+        match errors.next() {
+            Some(e) => Err(format!("Pin VecAtari worker failed: {}", e)),
+            None => Ok(vec_env),
+        }
+    }
+
+    // panics when an env panicked, see try_step
+    pub fn step(&mut self, actions: Vec<Action>) -> Vec<StepResult> {
+        self.try_step(actions).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // step all envs; a panic of an env is returned as an error, and every later call fails
+    // too unless the env is re-created
+    pub fn try_step(&mut self, actions: Vec<Action>) -> Result<Vec<StepResult>, EnvError> {
+        assert_eq!(
+            actions.len(),
+            self.num_envs(),
+            "Expected one action per env"
+        );
+        unsafe {
+            *self.shared.actions.get() = actions;
+        }
+        self.run(Command::Step)
+    }
+
+    // panics when an env panicked, see try_reset
+    pub fn reset(&mut self, seed: Option<i32>) -> Vec<StepResult> {
+        self.try_reset(seed).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // reset all envs; a given base seed reseeds env i with derive_seed(seed, i)
+    pub fn try_reset(&mut self, seed: Option<i32>) -> Result<Vec<StepResult>, EnvError> {
+        if let Some(seed) = seed {
+            for (i, env_seed) in self.seeds.iter_mut().enumerate() {
+                *env_seed = derive_seed(seed, i);
+            }
         }
+        self.run(Command::Reset(seed))
     }
 
-    pub fn step(
+    // write observations straight into ring from now on, leaving StepResult::obs empty; the
+    // ring needs one env per env of the batch and the observation shape of the batch
+    #[cfg(unix)]
+    pub fn attach_obs_ring(&mut self, ring: Arc<ObsRing>) -> Result<(), String> {
+        if ring.num_envs() != self.num_envs() {
+            return Err(format!(
+                "Observation ring holds {} envs, expected {}",
+                ring.num_envs(),
+                self.num_envs()
+            ));
+        }
+        if ring.screen_dim() != self.screen_size || ring.channels() != self.channels {
+            return Err(format!(
+                "Observation ring holds {:?} observations with {} channels, expected {:?} with {}",
+                ring.screen_dim(),
+                ring.channels(),
+                self.screen_size,
+                self.channels
+            ));
+        }
+        // workers only read the ring while handling a command
+        unsafe {
+            *self.shared.ring.get() = Some(ring);
+        }
+        Ok(())
+    }
+
+    // return observations in StepResult::obs again
+    #[cfg(unix)]
+    pub fn detach_obs_ring(&mut self) -> Option<Arc<ObsRing>> {
+        unsafe { (*self.shared.ring.get()).take() }
+    }
+
+    // record the episodes of every env from its next reset on, see Atari::start_recording;
+    // file names start with the index of the env, and re-created envs keep recording
+    pub fn start_recording(
         &mut self,
-        actions: Vec<i32>,
-    ) -> Vec<(usize, Vec<u8>, i32, bool, bool, bool, Option<i32>)> {
-        for (i, (env, &action)) in self.envs.iter().zip(&actions).enumerate() {
-            let env = env.clone();
-            let sender = self.sender.clone();
-            self.pool.execute(move || {
-                let mut env = env.lock().unwrap();
-                let (reward, terminal, truncation, life_loss) = env.step(action);
-                let score = match terminal || truncation {
-                    true => {
-                        let score = env.get_score();
-                        env.reset();
-                        Some(score)
-                    }
-                    false => None,
-                };
+        dir: impl AsRef<Path>,
+        options: RecordingOptions,
+    ) -> io::Result<()> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        unsafe {
+            *self.shared.recording.get() = Some((dir.as_ref().to_path_buf(), options));
+        }
+        self.record()
+    }
 
-                let obs = env.obs();
-                sender
-                    .send((i, obs, reward, terminal, truncation, life_loss, score))
-                    .unwrap();
-            });
+    // write the open episodes as truncated and return the first error met while recording
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        unsafe {
+            *self.shared.recording.get() = None;
         }
+        self.record()
+    }
 
-        let receiver = self.receiver.lock().unwrap();
-        let mut result: Vec<(usize, Vec<u8>, i32, bool, bool, bool, Option<i32>)> =
-            (0..self.envs.len())
-                .map(|_| receiver.recv().unwrap())
-                .collect();
-        result.sort_by_key(|x| x.0);
-        result
+    fn record(&mut self) -> io::Result<()> {
+        // envs that failed for good are not recorded, their error is reported on later calls
+        self.dispatch(Command::Record).map_err(io::Error::other)?;
+        for error in self.shared.recording_errors.iter() {
+            if let Some(error) = unsafe { (*error.get()).take() } {
+                return Err(error);
+            }
+        }
+        Ok(())
     }
 
-    pub fn reset(&mut self) -> Vec<(usize, Vec<u8>, i32, bool, bool, bool, Option<i32>)> {
-        for (i, env) in self.envs.iter().enumerate() {
-            let mut env = env.lock().unwrap();
-            env.reset();
-            self.sender
-                .send((i, env.obs(), 0, false, false, false, None))
-                .unwrap();
+    // return the panics of envs that were re-created since the last call
+    pub fn take_recovered_errors(&mut self) -> Vec<EnvError> {
+        std::mem::take(&mut self.recovered_errors)
+    }
+
+    // hand a command to the workers and collect the results once all are done
+    fn run(&mut self, command: Command) -> Result<Vec<StepResult>, EnvError> {
+        self.dispatch(command)?;
+        Ok(self
+            .shared
+            .results
+            .iter()
+            .map(|result| unsafe { (*result.get()).clone() })
+            .collect())
+    }
+
+    // run a command on the workers and return the first panic of an env that is not recovered
+    fn dispatch(&mut self, command: Command) -> Result<(), EnvError> {
+        // the barriers would wait forever for a worker that is gone
+        if let Some(w) = self.workers.iter().position(|worker| worker.is_finished()) {
+            let index = self.worker_envs[w].start;
+            return Err(EnvError {
+                index,
+                game: self.games[index],
+                message: format!("worker {} of envs {:?} has exited", w, self.worker_envs[w]),
+            });
+        }
+        unsafe {
+            *self.shared.command.get() = command;
+        }
+        self.shared.start.wait();
+        self.shared.done.wait();
+
+        let mut error = None;
+        for (index, failure) in self.shared.failures.iter().enumerate() {
+            if let Some(failure) = unsafe { (*failure.get()).take() } {
+                let env_error = EnvError {
+                    index,
+                    game: self.games[index],
+                    message: failure.message,
+                };
+                match failure.recovered {
+                    true => self.recovered_errors.push(env_error),
+                    false => {
+                        error.get_or_insert(env_error);
+                    }
+                }
+            }
         }
+        match error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
 
-        let receiver = self.receiver.lock().unwrap();
-        let mut result: Vec<(usize, Vec<u8>, i32, bool, bool, bool, Option<i32>)> =
-            (0..self.envs.len())
-                .map(|_| receiver.recv().unwrap())
-                .collect();
-        result.sort_by_key(|x| x.0);
-        result
+    pub fn num_envs(&self) -> usize {
+        self.games.len()
     }
 
-    pub fn action_space(&self) -> &Vec<i32> {
+    // return the game run by each env
+    pub fn games(&self) -> &[BundledRom] {
+        &self.games
+    }
+
+    pub fn action_space(&self) -> &[Action] {
         &self.action_space
     }
+
+    // return the seed each env is currently running with
+    pub fn seeds(&self) -> Vec<i32> {
+        self.seeds.clone()
+    }
+
+    // return (height, width) of the observations, shared by all envs
+    pub fn screen_dim(&self) -> (usize, usize) {
+        self.screen_size
+    }
 }
 
 impl Drop for VecAtari {
     fn drop(&mut self) {
-        for env in self.envs.iter() {
-            let mut env = env.lock().unwrap();
-            env.close();
+        // without all workers at the barrier, the others are left parked instead
+        if self.workers.iter().any(|worker| worker.is_finished()) {
+            return;
+        }
+        unsafe {
+            *self.shared.command.get() = Command::Close;
+        }
+        self.shared.start.wait();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+// number of workers for num_envs envs, checking the worker config
+pub(crate) fn worker_count(workers: &WorkerConfig, num_envs: usize) -> Result<usize, String> {
+    if workers.num_workers == Some(0) {
+        return Err("VecAtari needs at least one worker".to_string());
+    }
+    if let Some(cpus) = &workers.cpus {
+        check_cpus(cpus)?;
+    }
+    Ok(workers
+        .num_workers
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .min(num_envs))
+}
+
+// contiguous envs of each worker; the first num_envs % num_workers workers get one more env,
+// so that every worker has at least one
+pub(crate) fn worker_envs(num_envs: usize, num_workers: usize) -> Vec<Range<usize>> {
+    let (size, extra) = (num_envs / num_workers, num_envs % num_workers);
+    let mut start = 0;
+    (0..num_workers)
+        .map(|w| {
+            let end = start + size + (w < extra) as usize;
+            let envs = start..end;
+            start = end;
+            envs
+        })
+        .collect()
+}
+
+// action space and observation shape shared by envs, an error when their action sets differ
+pub(crate) fn shared_spaces(envs: &[Atari]) -> Result<(Vec<Action>, (usize, usize)), String> {
+    let action_space = envs[0].get_action_set();
+    if let Some(env) = envs.iter().find(|env| env.get_action_set() != action_space) {
+        return Err(format!(
+            "{} and {} have different action sets, enable full_action_space to mix them",
+            envs[0].rom(),
+            env.rom()
+        ));
+    }
+    let screen_size = envs
+        .iter()
+        .map(|env| env.screen_dim())
+        .fold((0, 0), |(h, w), (eh, ew)| (h.max(eh), w.max(ew)));
+    Ok((action_space, screen_size))
+}
+
+// one factory per spec, the mode and difficulty of a spec override those of the config
+pub(crate) fn env_factories(
+    specs: &[EnvSpec],
+    max_frames: u32,
+    gray_scale: bool,
+    max_episode_steps: Option<u32>,
+    config: &AleConfig,
+) -> Vec<MakeEnv> {
+    specs
+        .iter()
+        .map(|spec| {
+            let game = spec.game;
+            let mut config = config.clone();
+            config.mode = spec.mode.or(config.mode);
+            config.difficulty = spec.difficulty.or(config.difficulty);
+            let make_env: MakeEnv = Box::new(move |seed| {
+                Atari::try_new(
+                    game.name(),
+                    max_frames,
+                    gray_scale,
+                    Some(seed),
+                    max_episode_steps,
+                    Some(config.clone()),
+                )
+            });
+            make_env
+        })
+        .collect()
+}
+
+// check that every CPU to pin workers to is one this process may run on
+#[cfg(target_os = "linux")]
+fn check_cpus(cpus: &[usize]) -> Result<(), String> {
+    if cpus.is_empty() {
+        return Err("The CPU list to pin workers to is empty".to_string());
+    }
+    let mut allowed: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let err =
+        unsafe { libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut allowed) };
+    if err != 0 {
+        return Err(format!(
+            "Get the CPUs of this process failed: {}",
+            io::Error::last_os_error()
+        ));
+    }
+    match cpus.iter().find(|&&cpu| {
+        cpu >= libc::CPU_SETSIZE as usize || !unsafe { libc::CPU_ISSET(cpu, &allowed) }
+    }) {
+        Some(cpu) => Err(format!("CPU {} is not available to this process", cpu)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn check_cpus(_cpus: &[usize]) -> Result<(), String> {
+    Err("Pinning VecAtari workers to CPUs is only supported on Linux".to_string())
+}
+
+// pin the calling thread, or process when single-threaded, to a CPU checked by check_cpus
+#[cfg(target_os = "linux")]
+pub(crate) fn pin_current_thread(cpu: usize) -> io::Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        match libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn pin_current_thread(_cpu: usize) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// step or reset the envs of one worker on every command, until asked to close; panics of an
+// env are caught so that the worker always reaches the done barrier
+fn run_worker(
+    shared: Arc<Shared>,
+    mut slots: Vec<Slot>,
+    screen_size: (usize, usize),
+    channels: usize,
+    recreate: bool,
+) {
+    loop {
+        shared.start.wait();
+        let command = unsafe { *shared.command.get() };
+        if let Command::Close = command {
+            break;
+        }
+        let actions = unsafe { &*shared.actions.get() };
+        let ring = unsafe { (*shared.ring.get()).as_deref() };
+        let recording = unsafe { &*shared.recording.get() };
+        if let Command::Record = command {
+            for slot in slots.iter_mut().filter(|slot| slot.failure.is_none()) {
+                let run = panic::catch_unwind(AssertUnwindSafe(|| match recording {
+                    Some((dir, options)) => slot.env.record(dir, Some(slot.index), options.clone()),
+                    None => slot.env.stop_recording(),
+                }));
+                // an env that panicked here is left in an unknown state and fails later calls
+                let recorded = run.unwrap_or_else(|payload| {
+                    let message = panic_message(payload);
+                    slot.failure = Some(message.clone());
+                    Err(io::Error::other(EnvError {
+                        index: slot.index,
+                        game: slot.env.rom(),
+                        message,
+                    }))
+                });
+                unsafe {
+                    *shared.recording_errors[slot.index].get() = recorded.err();
+                }
+            }
+            shared.done.wait();
+            continue;
+        }
+        for slot in slots.iter_mut() {
+            // only this worker touches the result and failure of its envs between the barriers
+            let result = unsafe { &mut *shared.results[slot.index].get() };
+            let failure = unsafe { &mut *shared.failures[slot.index].get() };
+            if let Some(message) = &slot.failure {
+                *failure = Some(Failure {
+                    message: format!("failed in an earlier call: {}", message),
+                    recovered: false,
+                });
+                continue;
+            }
+
+            let run = panic::catch_unwind(AssertUnwindSafe(|| {
+                run_command(slot, command, actions, result, ring, screen_size, channels)
+            }));
+            let Err(payload) = run else {
+                continue;
+            };
+            let message = panic_message(payload);
+            let recovered = recreate
+                && panic::catch_unwind(AssertUnwindSafe(|| {
+                    // the episode ends at the observation last handed out for the env
+                    let obs_size = screen_size.0 * screen_size.1 * channels;
+                    let final_obs = last_obs(slot.index, result, ring, obs_size);
+                    let score = slot.env.get_score();
+                    slot.env = (slot.make_env)(slot.seed).unwrap_or_else(|e| panic!("{}", e));
+                    if let Some((dir, options)) = recording {
+                        // a new env has no recording to stop and the directory exists, so this cannot fail
+                        let _ = slot.env.record(dir, Some(slot.index), options.clone());
+                    }
+                    result.score = Some(score);
+                    result.final_obs = Some(final_obs);
+                    result.reward = 0;
+                    result.terminal = false;
+                    result.truncation = true;
+                    result.life_loss = false;
+                    emit_obs(slot, result, ring, screen_size, channels);
+                }))
+                .is_ok();
+            if !recovered {
+                slot.failure = Some(message.clone());
+            }
+            *failure = Some(Failure { message, recovered });
+        }
+        shared.done.wait();
+    }
+}
+
+fn run_command(
+    slot: &mut Slot,
+    command: Command,
+    actions: &[Action],
+    result: &mut StepResult,
+    ring: Option<&ObsRing>,
+    screen_size: (usize, usize),
+    channels: usize,
+) {
+    match command {
+        Command::Step => {
+            let (reward, terminal, truncation, life_loss) = slot.env.step(actions[slot.index]);
+            (result.score, result.final_obs) = match terminal || truncation {
+                true => {
+                    let score = slot.env.get_score();
+                    let mut final_obs = vec![0u8; screen_size.0 * screen_size.1 * channels];
+                    write_obs(&mut slot.env, &mut final_obs, screen_size, channels);
+                    slot.env.reset(None);
+                    (Some(score), Some(final_obs))
+                }
+                false => (None, None),
+            };
+            result.reward = reward;
+            result.terminal = terminal;
+            result.truncation = truncation;
+            result.life_loss = life_loss;
+        }
+        Command::Reset(seed) => {
+            if let Some(seed) = seed {
+                slot.seed = derive_seed(seed, slot.index);
+            }
+            slot.env.reset(seed.map(|_| slot.seed));
+            result.score = None;
+            result.final_obs = None;
+            result.reward = 0;
+            result.terminal = false;
+            result.truncation = false;
+            result.life_loss = false;
+        }
+        Command::Record | Command::Close => unreachable!(),
+    }
+    emit_obs(slot, result, ring, screen_size, channels);
+}
+
+// write the observation of an env into the ring when one is attached, else into its result
+fn emit_obs(
+    slot: &mut Slot,
+    result: &mut StepResult,
+    ring: Option<&ObsRing>,
+    screen_size: (usize, usize),
+    channels: usize,
+) {
+    match ring {
+        Some(ring) => {
+            result.obs.clear();
+            ring.push(slot.index, |obs| {
+                write_obs(&mut slot.env, obs, screen_size, channels)
+            });
+        }
+        None => {
+            result
+                .obs
+                .resize(screen_size.0 * screen_size.1 * channels, 0);
+            write_obs(&mut slot.env, &mut result.obs, screen_size, channels);
         }
-        self.pool.join();
+    }
+}
+
+// observation of an env in the ring, else in its result, as written by the last call
+fn last_obs(index: usize, result: &StepResult, ring: Option<&ObsRing>, obs_size: usize) -> Vec<u8> {
+    let mut obs = vec![0u8; obs_size];
+    match ring {
+        Some(ring) => {
+            let head = ring.head(index);
+            if head > 0 {
+                ring.read(index, head - 1, &mut obs);
+            }
+        }
+        None if result.obs.len() == obs_size => obs.copy_from_slice(&result.obs),
+        None => (),
+    }
+    obs
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
+
+// write the screen of an env into obs, zero padded at the bottom and right when it is smaller
+// than the shared observation shape
+pub(crate) fn write_obs(env: &mut Atari, obs: &mut [u8], to: (usize, usize), channels: usize) {
+    if env.screen_dim() == to {
+        env.obs_into(obs);
+        return;
+    }
+    let screen = env.obs();
+    let row = env.screen_dim().1 * channels;
+    for (y, src) in screen.chunks_exact(row).enumerate() {
+        let start = y * to.1 * channels;
+        obs[start..start + row].copy_from_slice(src);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn worker_envs_are_spread_evenly() {
+        assert_eq!(worker_envs(10, 6), [0..2, 2..4, 4..6, 6..8, 8..9, 9..10]);
+        assert_eq!(worker_envs(8, 4), [0..2, 2..4, 4..6, 6..8]);
+        assert_eq!(worker_envs(3, 3), [0..1, 1..2, 2..3]);
+    }
+
+    #[test]
+    fn unavailable_cpus_are_an_error() {
+        let workers = WorkerConfig {
+            num_workers: Some(2),
+            cpus: Some(vec![0, 1 << 20]),
+            ..WorkerConfig::default()
+        };
+        let specs = vec![EnvSpec::from(BundledRom::Pong); 2];
+        assert!(VecAtari::try_with_specs(specs, 1000, true, 0, None, None, Some(workers)).is_err());
+    }
+
+    #[test]
+    fn uneven_workers_step_and_drop() {
+        let workers = WorkerConfig {
+            num_workers: Some(6),
+            ..WorkerConfig::default()
+        };
+        let specs = vec![EnvSpec::from(BundledRom::Pong); 10];
+        let mut envs = VecAtari::with_specs(specs, 1000, true, 0, None, None, Some(workers));
+        assert_eq!(envs.workers.len(), 6);
+        assert_eq!(envs.reset(None).len(), 10);
+        let results = envs.step(vec![Action::Noop; 10]);
+        assert!(results
+            .iter()
+            .enumerate()
+            .all(|(i, result)| result.index == i));
     }
 }
@@ -0,0 +1,597 @@
+//! Episode videos written in pure Rust, so that headless nodes need no ffmpeg.
+//!
+//! Frames are upscaled by an integer factor and can carry a line of text with the step,
+//! reward, score and action, drawn with a built-in 3×5 pixel font. Frames of all formats are
+//! streamed to disk; APNG announces its frame count before the first frame, so the count is
+//! written as 0 and patched in when the video is finished.
+
+use crate::action::Action;
+use crate::trajectory::Trajectory;
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+// offset of the acTL chunk, after the signature and the 13 byte IHDR chunk
+const ACTL_OFFSET: u64 = 8 + 12 + 13;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// Animated GIF, with an exact palette when a frame has at most 256 colors.
+    Gif,
+    /// Animated PNG, lossless.
+    Apng,
+    /// Raw YUV 4:4:4 frames, readable by ffmpeg and most players.
+    Y4m,
+}
+
+impl VideoFormat {
+    /// Format for a file extension: `gif`, `png` or `apng`, and `y4m`.
+    pub fn from_path(path: &Path) -> Option<VideoFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gif" => Some(VideoFormat::Gif),
+            "png" | "apng" => Some(VideoFormat::Apng),
+            "y4m" => Some(VideoFormat::Y4m),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VideoOptions {
+    /// Frames per second. GIF delays are in 10 ms and browsers slow down delays below 20 ms,
+    /// so GIFs play at most 50 frames per second.
+    pub fps: u32,
+    /// Integer upscaling factor of the screen.
+    pub scale: usize,
+    /// Draw the `FrameInfo` given with each frame.
+    pub overlay: bool,
+}
+
+impl Default for VideoOptions {
+    fn default() -> Self {
+        VideoOptions {
+            fps: 60,
+            scale: 2,
+            overlay: true,
+        }
+    }
+}
+
+/// Text drawn over a frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameInfo {
+    pub step: u32,
+    /// Reward of the step that led to the frame.
+    pub reward: i32,
+    pub score: i32,
+    /// Action taken from the frame.
+    pub action: Option<Action>,
+}
+
+impl FrameInfo {
+    fn text(&self) -> String {
+        let mut text = format!("STEP {} R {} SCORE {}", self.step, self.reward, self.score);
+        if let Some(action) = self.action {
+            text.push(' ');
+            text.push_str(action.meaning());
+        }
+        text
+    }
+}
+
+enum Encoder {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Apng(BufWriter<File>),
+    Y4m(BufWriter<File>),
+}
+
+pub struct VideoWriter {
+    encoder: Encoder,
+    screen_size: (usize, usize),
+    channels: usize,
+    options: VideoOptions,
+    frames: u64,
+}
+
+impl VideoWriter {
+    /// Video of `(height, width)` frames with 1 (grayscale) or 3 (RGB) channels, in the
+    /// format given by the extension of `path`.
+    pub fn create(
+        path: impl AsRef<Path>,
+        screen_size: (usize, usize),
+        channels: usize,
+        options: VideoOptions,
+    ) -> io::Result<VideoWriter> {
+        let path = path.as_ref();
+        let format = VideoFormat::from_path(path).ok_or_else(|| {
+            invalid(format!(
+                "Unknown video format of {}, use .gif, .png or .y4m",
+                path.display()
+            ))
+        })?;
+        if channels != 1 && channels != 3 {
+            return Err(invalid("Frames have 1 or 3 channels".to_string()));
+        }
+        if options.fps == 0 || options.scale == 0 {
+            return Err(invalid("fps and scale must be at least 1".to_string()));
+        }
+        let (height, width) = (screen_size.0 * options.scale, screen_size.1 * options.scale);
+        if height > u16::MAX as usize || width > u16::MAX as usize {
+            return Err(invalid("Video frames are too large".to_string()));
+        }
+
+        let mut file = BufWriter::new(File::create(path)?);
+        let encoder = match format {
+            VideoFormat::Gif => {
+                let mut encoder =
+                    gif::Encoder::new(file, width as u16, height as u16, &[]).map_err(gif_error)?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(gif_error)?;
+                Encoder::Gif(encoder)
+            }
+            VideoFormat::Apng => {
+                let mut header = Vec::with_capacity(13);
+                header.extend_from_slice(&(width as u32).to_be_bytes());
+                header.extend_from_slice(&(height as u32).to_be_bytes());
+                // 8 bit RGB, deflate, no interlacing
+                header.extend_from_slice(&[8, 2, 0, 0, 0]);
+                file.write_all(&PNG_SIGNATURE)?;
+                write_chunk(&mut file, b"IHDR", &header)?;
+                // frames and plays, 0 plays loop forever
+                write_chunk(&mut file, b"acTL", &[0; 8])?;
+                Encoder::Apng(file)
+            }
+            VideoFormat::Y4m => {
+                writeln!(
+                    file,
+                    "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                    width, height, options.fps
+                )?;
+                Encoder::Y4m(file)
+            }
+        };
+        Ok(VideoWriter {
+            encoder,
+            screen_size,
+            channels,
+            options,
+            frames: 0,
+        })
+    }
+
+    /// Appends a frame, with `info` drawn over it when the overlay is enabled.
+    pub fn push(&mut self, frame: &[u8], info: Option<&FrameInfo>) -> io::Result<()> {
+        let (height, width) = self.screen_size;
+        if frame.len() != height * width * self.channels {
+            return Err(invalid("Frame has the wrong size".to_string()));
+        }
+        let scale = self.options.scale;
+        let (height, width) = (height * scale, width * scale);
+        let mut rgb = upscale(frame, self.screen_size, self.channels, scale);
+        if let (true, Some(info)) = (self.options.overlay, info) {
+            draw_text(&mut rgb, width, &info.text(), scale);
+        }
+
+        match &mut self.encoder {
+            Encoder::Gif(encoder) => {
+                let mut frame = gif_frame(&rgb, width as u16, height as u16);
+                // delays are in 10 ms, round the end time of each frame
+                let fps = self.options.fps.min(50) as u64;
+                let end = ((self.frames + 1) * 100 + fps / 2) / fps;
+                let start = (self.frames * 100 + fps / 2) / fps;
+                frame.delay = (end - start) as u16;
+                encoder.write_frame(&frame).map_err(gif_error)?;
+            }
+            Encoder::Apng(file) => {
+                // frame control and data chunks share one sequence, the first frame is the
+                // IDAT image shown by viewers without APNG support
+                let sequence = match self.frames {
+                    0 => 0,
+                    i => 2 * i as u32 - 1,
+                };
+                let mut control = Vec::with_capacity(26);
+                for value in [sequence, width as u32, height as u32, 0, 0] {
+                    control.extend_from_slice(&value.to_be_bytes());
+                }
+                let delay = self.options.fps.min(u16::MAX as u32) as u16;
+                control.extend_from_slice(&1u16.to_be_bytes());
+                control.extend_from_slice(&delay.to_be_bytes());
+                control.extend_from_slice(&[0, 0]);
+                write_chunk(file, b"fcTL", &control)?;
+
+                let mut data = Vec::new();
+                if self.frames > 0 {
+                    data.extend_from_slice(&(sequence + 1).to_be_bytes());
+                }
+                let mut encoder = ZlibEncoder::new(data, Compression::default());
+                // each scanline starts with filter type 0, none
+                for row in rgb.chunks_exact(width * 3) {
+                    encoder.write_all(&[0])?;
+                    encoder.write_all(row)?;
+                }
+                let data = encoder.finish()?;
+                match self.frames {
+                    0 => write_chunk(file, b"IDAT", &data)?,
+                    _ => write_chunk(file, b"fdAT", &data)?,
+                }
+            }
+            Encoder::Y4m(file) => {
+                file.write_all(b"FRAME\n")?;
+                file.write_all(&rgb_to_yuv444(&rgb))?;
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Completes the file; a video without frames is an error for APNG only.
+    pub fn finish(self) -> io::Result<()> {
+        match self.encoder {
+            Encoder::Gif(encoder) => encoder.into_inner()?.flush(),
+            Encoder::Y4m(mut file) => file.flush(),
+            Encoder::Apng(mut file) => {
+                if self.frames == 0 {
+                    return Err(invalid("APNG videos need at least one frame".to_string()));
+                }
+                let frames = u32::try_from(self.frames)
+                    .map_err(|_| invalid("Too many frames for APNG".to_string()))?;
+                write_chunk(&mut file, b"IEND", &[])?;
+                let mut control = frames.to_be_bytes().to_vec();
+                control.extend_from_slice(&[0; 4]);
+                file.seek(SeekFrom::Start(ACTL_OFFSET))?;
+                write_chunk(&mut file, b"acTL", &control)?;
+                file.flush()
+            }
+        }
+    }
+}
+
+/// Writes a recorded episode as a video, each frame showing the action taken from it.
+pub fn trajectory_video(
+    trajectory: &Trajectory,
+    path: impl AsRef<Path>,
+    options: VideoOptions,
+) -> io::Result<()> {
+    let mut writer =
+        VideoWriter::create(path, trajectory.screen_size, trajectory.channels, options)?;
+    let mut info = FrameInfo::default();
+    for t in 0..trajectory.len() {
+        info.step = t as u32;
+        if t > 0 {
+            info.reward = trajectory.rewards[t - 1];
+            info.score += info.reward;
+        }
+        // the last row has no action
+        info.action = (t + 1 < trajectory.len()).then(|| trajectory.actions[t]);
+        writer.push(trajectory.obs_at(t), Some(&info))?;
+    }
+    writer.finish()
+}
+
+// records the frames of an env, keeping the first error until the video is finished
+pub(crate) struct VideoRecorder {
+    writer: Option<VideoWriter>,
+    error: Option<io::Error>,
+}
+
+impl VideoRecorder {
+    pub(crate) fn new(writer: VideoWriter) -> VideoRecorder {
+        VideoRecorder {
+            writer: Some(writer),
+            error: None,
+        }
+    }
+
+    pub(crate) fn push(&mut self, frame: &[u8], info: &FrameInfo) {
+        if let Some(writer) = self.writer.as_mut() {
+            if let Err(e) = writer.push(frame, Some(info)) {
+                self.writer = None;
+                self.error = Some(e);
+            }
+        }
+    }
+
+    pub(crate) fn finish(self) -> io::Result<()> {
+        match (self.writer, self.error) {
+            (_, Some(e)) => Err(e),
+            (Some(writer), None) => writer.finish(),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+// nearest neighbour upscaling to RGB
+fn upscale(frame: &[u8], screen_size: (usize, usize), channels: usize, scale: usize) -> Vec<u8> {
+    let (height, width) = screen_size;
+    let mut rgb = Vec::with_capacity(height * width * scale * scale * 3);
+    for y in 0..height {
+        let row_start = rgb.len();
+        for x in 0..width {
+            let pixel = &frame[(y * width + x) * channels..(y * width + x + 1) * channels];
+            let pixel = match channels {
+                1 => [pixel[0]; 3],
+                _ => [pixel[0], pixel[1], pixel[2]],
+            };
+            for _ in 0..scale {
+                rgb.extend_from_slice(&pixel);
+            }
+        }
+        for _ in 1..scale {
+            rgb.extend_from_within(row_start..row_start + width * scale * 3);
+        }
+    }
+    rgb
+}
+
+// white text on a black band at the top left, each font pixel drawn as scale × scale
+fn draw_text(rgb: &mut [u8], width: usize, text: &str, scale: usize) {
+    let height = rgb.len() / (width * 3);
+    let band = (GLYPH_HEIGHT + 2) * scale;
+    let text_width = ((text.len() * (GLYPH_WIDTH + 1) + 1) * scale).min(width);
+    for y in 0..band.min(height) {
+        rgb[y * width * 3..(y * width + text_width) * 3].fill(0);
+    }
+    for (i, c) in text.chars().enumerate() {
+        let rows = glyph(c);
+        for (gy, row) in rows.iter().enumerate() {
+            for gx in 0..GLYPH_WIDTH {
+                if row >> (GLYPH_WIDTH - 1 - gx) & 1 == 0 {
+                    continue;
+                }
+                let x0 = (1 + i * (GLYPH_WIDTH + 1) + gx) * scale;
+                let y0 = (1 + gy) * scale;
+                for y in y0..(y0 + scale).min(height) {
+                    for x in x0..(x0 + scale).min(width) {
+                        rgb[(y * width + x) * 3..(y * width + x + 1) * 3].fill(255);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// GIF frame with an exact palette when the frame has few enough colors, else quantized
+fn gif_frame(rgb: &[u8], width: u16, height: u16) -> gif::Frame<'static> {
+    let mut palette: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut pixels = Vec::with_capacity(rgb.len() / 3);
+    for pixel in rgb.chunks_exact(3) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let next = palette.len();
+        if next == 256 && !palette.contains_key(&color) {
+            return gif::Frame::from_rgb_speed(width, height, rgb, 10);
+        }
+        pixels.push(*palette.entry(color).or_insert(next as u8));
+    }
+    let mut colors = vec![0u8; palette.len() * 3];
+    for (color, index) in palette {
+        colors[index as usize * 3..index as usize * 3 + 3].copy_from_slice(&color);
+    }
+    gif::Frame::from_palette_pixels(width, height, pixels, colors, None)
+}
+
+// planar Y, U and V with BT.601 limited range coefficients
+fn rgb_to_yuv444(rgb: &[u8]) -> Vec<u8> {
+    let n = rgb.len() / 3;
+    let mut yuv = vec![0u8; 3 * n];
+    for (i, pixel) in rgb.chunks_exact(3).enumerate() {
+        let (r, g, b) = (pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+        yuv[i] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+        yuv[n + i] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+        yuv[2 * n + i] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+    }
+    yuv
+}
+
+// PNG chunk: length, type, data and the CRC of type and data
+fn write_chunk(file: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+    file.write_all(&(data.len() as u32).to_be_bytes())?;
+    file.write_all(kind)?;
+    file.write_all(data)?;
+    file.write_all(&crc.sum().to_be_bytes())
+}
+
+fn gif_error(e: gif::EncodingError) -> io::Error {
+    match e {
+        gif::EncodingError::Io(e) => e,
+        e => io::Error::other(e),
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+
+// rows of a 3×5 glyph, most significant bit on the left; unknown characters are blank
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        _ => [0; GLYPH_HEIGHT],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const FRAMES: usize = 3;
+
+    // writes FRAMES 2×3 gray frames, frame i all of value 40 * i, without the overlay
+    fn write(dir: &Path, name: &str, scale: usize) -> io::Result<std::path::PathBuf> {
+        let path = dir.join(name);
+        let options = VideoOptions {
+            fps: 30,
+            scale,
+            overlay: false,
+        };
+        let mut writer = VideoWriter::create(&path, (2, 3), 1, options)?;
+        for i in 0..FRAMES {
+            writer.push(&[40 * i as u8; 6], None)?;
+        }
+        assert_eq!(writer.frames(), FRAMES as u64);
+        writer.finish()?;
+        Ok(path)
+    }
+
+    #[test]
+    fn formats_follow_the_extension() {
+        let format = |name: &str| VideoFormat::from_path(Path::new(name));
+        assert_eq!(format("a.gif"), Some(VideoFormat::Gif));
+        assert_eq!(format("a.PNG"), Some(VideoFormat::Apng));
+        assert_eq!(format("a.apng"), Some(VideoFormat::Apng));
+        assert_eq!(format("a.y4m"), Some(VideoFormat::Y4m));
+        assert_eq!(format("a.mp4"), None);
+        assert_eq!(format("a"), None);
+    }
+
+    #[test]
+    fn apng_frames_round_trip() {
+        let dir = tempdir::TempDir::new("ale-env-video").unwrap();
+        let path = write(dir.path(), "video.png", 2).unwrap();
+        let decoder = png::Decoder::new(File::open(path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!((info.width, info.height), (6, 4));
+        let control = info.animation_control.unwrap();
+        assert_eq!((control.num_frames, control.num_plays), (FRAMES as u32, 0));
+        let mut buffer = vec![0u8; reader.output_buffer_size()];
+        for i in 0..FRAMES {
+            let frame = reader.next_frame(&mut buffer).unwrap();
+            assert_eq!(frame.buffer_size(), 6 * 4 * 3);
+            assert!(buffer.iter().all(|&v| v == 40 * i as u8));
+            let control = reader.info().frame_control.unwrap();
+            assert_eq!((control.delay_num, control.delay_den), (1, 30));
+        }
+    }
+
+    #[test]
+    fn apng_without_frames_is_an_error() {
+        let dir = tempdir::TempDir::new("ale-env-video").unwrap();
+        let path = dir.path().join("video.png");
+        let writer = VideoWriter::create(&path, (2, 3), 1, VideoOptions::default()).unwrap();
+        assert!(writer.finish().is_err());
+    }
+
+    #[test]
+    fn gif_frames_round_trip() {
+        let dir = tempdir::TempDir::new("ale-env-video").unwrap();
+        let path = write(dir.path(), "video.gif", 1).unwrap();
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(File::open(path).unwrap()).unwrap();
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            let i = delays.len() as u8;
+            assert_eq!((frame.width, frame.height), (3, 2));
+            assert_eq!(frame.buffer, [40 * i, 40 * i, 40 * i, 255].repeat(6));
+            delays.push(frame.delay);
+        }
+        // 30 fps in 10 ms steps
+        assert_eq!(delays, [3, 4, 3]);
+    }
+
+    #[test]
+    fn y4m_frames_are_planar_yuv() {
+        let dir = tempdir::TempDir::new("ale-env-video").unwrap();
+        let path = write(dir.path(), "video.y4m", 1).unwrap();
+        let data = fs::read(path).unwrap();
+        let header = b"YUV4MPEG2 W3 H2 F30:1 Ip A1:1 C444\n";
+        assert!(data.starts_with(header));
+        let frames = &data[header.len()..];
+        assert_eq!(frames.len(), FRAMES * (6 + 3 * 6));
+        for (i, frame) in frames.chunks_exact(6 + 3 * 6).enumerate() {
+            assert_eq!(&frame[..6], b"FRAME\n");
+            // gray has no chroma
+            assert_eq!(&frame[6 + 6..], [128; 12]);
+            let luma = rgb_to_yuv444(&[40 * i as u8; 3])[0];
+            assert_eq!(&frame[6..12], [luma; 6]);
+        }
+    }
+
+    #[test]
+    fn bad_videos_are_rejected_up_front() {
+        let dir = tempdir::TempDir::new("ale-env-video").unwrap();
+        let create = |name: &str, channels: usize, scale: usize| {
+            let options = VideoOptions {
+                scale,
+                ..VideoOptions::default()
+            };
+            VideoWriter::create(dir.path().join(name), (2, 3), channels, options)
+        };
+        assert!(create("video.mp4", 1, 1).is_err());
+        assert!(create("video.gif", 2, 1).is_err());
+        assert!(create("video.gif", 1, 0).is_err());
+        assert!(create("video.gif", 1, 30_000).is_err());
+        let mut writer = create("video.gif", 3, 1).unwrap();
+        assert!(writer.push(&[0; 6], None).is_err());
+    }
+
+    #[test]
+    fn overlay_text_is_drawn_at_the_top() {
+        let mut rgb = upscale(&[100; 10 * 40], (10, 40), 1, 1);
+        let info = FrameInfo {
+            step: 1,
+            ..FrameInfo::default()
+        };
+        draw_text(&mut rgb, 40, &info.text(), 1);
+        let pixel = |x: usize, y: usize| rgb[(y * 40 + x) * 3];
+        // a black band with the white S of STEP, and the screen below it
+        assert_eq!(pixel(0, 0), 0);
+        assert_eq!((pixel(1, 1), pixel(2, 1), pixel(1, 2)), (0, 255, 255));
+        assert_eq!(pixel(0, 9), 100);
+    }
+}